@@ -0,0 +1,172 @@
+use crate::{
+    evaluate::{TokenType, tokenize},
+    prompt::Prompt,
+    undo::EditKind,
+};
+use std::{collections::HashMap, time::Instant};
+
+// fish style abbreviations, "gco" turns into "git checkout" right there in the prompt when you hit space
+#[derive(Default)]
+pub struct Abbreviations {
+    pub list: HashMap<String, String>,
+}
+
+impl Abbreviations {
+    pub fn new(list: HashMap<String, String>) -> Abbreviations {
+        Abbreviations { list }
+    }
+
+    // if the word right behind the cursor is an abbreviation (and it's where a command would go),
+    // swap it out. returns whether anything happened
+    pub fn expand(&self, prompt: &mut Prompt) -> bool {
+        let cursor = prompt.cursor_position;
+        let before = &prompt.text()[..cursor];
+        let start = before
+            .rfind(|c: char| c.is_whitespace() || c == '|' || c == '&')
+            .map_or(0, |i| i + 1);
+        let word = &before[start..];
+
+        // only unquoted words in command position, so `echo gco` & `"gco"` stay as they are
+        let tokens = prompt.tokens();
+        let mut tokens = tokens[..tokens.partition_point(|t| t.start < cursor)]
+            .iter()
+            .rev();
+        let is_word = tokens
+            .next()
+            .is_some_and(|t| t.end == cursor && t.contents == TokenType::Value(word.to_string()));
+        let in_command_position = tokens
+            .next()
+            .is_none_or(|t| matches!(t.contents, TokenType::Pipe | TokenType::AndThen));
+        if !is_word || !in_command_position {
+            return false;
+        }
+
+        let Some(expansion) = self.list.get(word) else {
+            return false;
+        };
+        prompt.replace_range(start, cursor, expansion, EditKind::Other);
+        prompt.expanded = Some((start, start + expansion.len(), Instant::now()));
+        true
+    }
+
+    // typing a space is what sets one off. returns whether it did
+    pub fn type_character(&self, prompt: &mut Prompt, c: char) -> bool {
+        let expanded = c == ' ' && self.expand(prompt);
+        prompt.type_character(c);
+        expanded
+    }
+
+    // the `abbr` builtin. returns None if the line isn't an abbr command, otherwise what to print.
+    //   abbr                      list them
+    //   abbr gco git checkout     add one
+    //   abbr -e gco               get rid of one
+    // TODO)) these only last until you close the shell, there's nowhere to save them yet
+    pub fn run_builtin(&mut self, line: &str) -> Option<Vec<String>> {
+        let tokens = tokenize(line);
+        let (command, args) = tokens.split_first()?;
+        if command.contents != TokenType::Value("abbr".to_string()) {
+            return None;
+        }
+
+        let output = match args {
+            [] => {
+                let mut list: Vec<_> = self.list.iter().collect();
+                list.sort();
+                list.into_iter()
+                    .map(|(name, expansion)| format!("abbr {name} '{expansion}'"))
+                    .collect()
+            }
+            [flag, names @ ..] if matches!(&flag.contents, TokenType::Flag(f) if f == "e" || f == "erase") => {
+                names
+                    .iter()
+                    .filter_map(|t| t.contents.not_a_symbol())
+                    .filter(|name| self.list.remove(*name).is_none())
+                    .map(|name| format!("abbr: no abbreviation named {name}"))
+                    .collect()
+            }
+            [name, ..] => {
+                let Some(name) = name.contents.not_a_symbol() else {
+                    return Some(vec!["abbr: expected a name".to_string()]);
+                };
+                // everything after the name, exactly as it was typed
+                let expansion = line[args[0].start..]
+                    .trim_start()
+                    .strip_prefix(name.as_str())
+                    .unwrap_or("")
+                    .trim();
+                if expansion.is_empty() {
+                    return Some(vec![format!("abbr: {name} needs something to expand to")]);
+                }
+                self.list.insert(name.clone(), expansion.to_string());
+                vec![]
+            }
+        };
+        Some(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{abbreviations::Abbreviations, prompt::Prompt};
+    use std::collections::HashMap;
+
+    fn expand(abbreviations: &Abbreviations, text: &str) -> String {
+        let mut p = Prompt::default();
+        p.insert_str(text, crate::undo::EditKind::Typing);
+        abbreviations.expand(&mut p);
+        p.text().to_string()
+    }
+
+    #[test]
+    fn expands_in_command_position() {
+        let abbreviations = Abbreviations::new(HashMap::from([(
+            "gco".to_string(),
+            "git checkout".to_string(),
+        )]));
+
+        assert_eq!(expand(&abbreviations, "gco"), "git checkout");
+        assert_eq!(expand(&abbreviations, "ls | gco"), "ls | git checkout");
+        assert_eq!(
+            expand(&abbreviations, "make && gco"),
+            "make && git checkout"
+        );
+        assert_eq!(expand(&abbreviations, "echo gco"), "echo gco");
+        assert_eq!(expand(&abbreviations, "\"gco"), "\"gco");
+        assert_eq!(expand(&abbreviations, "gcox"), "gcox");
+
+        // the flash keeps up with edits around it, and goes away once it gets edited itself
+        let mut p = Prompt::default();
+        p.insert_str("gco", crate::undo::EditKind::Typing);
+        assert!(abbreviations.type_character(&mut p, ' '));
+        let range = |p: &Prompt| p.expanded.map(|(start, end, _)| (start, end));
+        assert_eq!(range(&p), Some((0, 12)));
+        p.insert_str("main", crate::undo::EditKind::Typing);
+        p.cursor_position = 0;
+        p.insert_str("sudo ", crate::undo::EditKind::Typing);
+        assert_eq!(range(&p), Some((5, 17)));
+        p.cursor_position = 8;
+        p.insert_character('!');
+        assert_eq!(range(&p), None);
+    }
+
+    #[test]
+    fn abbr_builtin() {
+        let mut abbreviations = Abbreviations::default();
+        assert_eq!(abbreviations.run_builtin("ls -la"), None);
+
+        assert_eq!(
+            abbreviations.run_builtin("abbr gs git status  --short"),
+            Some(vec![])
+        );
+        assert_eq!(abbreviations.list["gs"], "git status  --short");
+        assert_eq!(
+            abbreviations.run_builtin("abbr"),
+            Some(vec!["abbr gs 'git status  --short'".to_string()])
+        );
+        assert_eq!(abbreviations.run_builtin("abbr -e gs"), Some(vec![]));
+        assert_eq!(
+            abbreviations.run_builtin("abbr --erase gs"),
+            Some(vec!["abbr: no abbreviation named gs".to_string()])
+        );
+    }
+}
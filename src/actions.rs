@@ -0,0 +1,111 @@
+use crate::{
+    keymap::Action,
+    misc_types::Direction,
+    prompt::{Prompt, WordCase},
+};
+
+// what running an action on the prompt did, so whoever ran it knows how hard to bump the chain
+#[derive(PartialEq, Debug)]
+pub enum Edited {
+    // needs more than just the prompt (history, the kill ring, the terminal, quitting...)
+    NotAnEdit,
+    Quietly,
+    Bump(f32, Direction),
+}
+
+fn bump_for(hit_wall: bool, direction: Direction) -> Edited {
+    Edited::Bump(if hit_wall { 30.0 } else { 10.0 }, direction)
+}
+
+// everything that only has to touch the prompt. main does the rest
+pub fn edit(prompt: &mut Prompt, action: Action) -> Edited {
+    match action {
+        Action::InsertNewline => prompt.insert_character('\n'),
+        Action::DeleteCharOrQuit if prompt.text().is_empty() => return Edited::NotAnEdit,
+        Action::DeleteCharOrQuit | Action::DeleteChar => {
+            return bump_for(prompt.delete_forward(), Direction::Right);
+        }
+        Action::Undo => return bump_for(prompt.undo(), Direction::Left),
+        Action::Redo => return bump_for(prompt.redo(), Direction::Right),
+        Action::SelectAll => prompt.select_all(),
+        Action::BeginningOfLine => {
+            let start = prompt.line_start();
+            return bump_for(prompt.move_to(start), Direction::Left);
+        }
+        Action::EndOfLine => {
+            let end = prompt.line_end();
+            return bump_for(prompt.move_to(end), Direction::Right);
+        }
+        Action::BackwardChar
+        | Action::ForwardChar
+        | Action::SelectBackwardChar
+        | Action::SelectForwardChar
+        | Action::BackwardSegment
+        | Action::ForwardSegment
+        | Action::SelectBackwardSegment
+        | Action::SelectForwardSegment => {
+            let direction = match action {
+                Action::BackwardChar
+                | Action::SelectBackwardChar
+                | Action::BackwardSegment
+                | Action::SelectBackwardSegment => Direction::Left,
+                _ => Direction::Right,
+            };
+            let shift = matches!(
+                action,
+                Action::SelectBackwardChar
+                    | Action::SelectForwardChar
+                    | Action::SelectBackwardSegment
+                    | Action::SelectForwardSegment
+            );
+            let ctrl = matches!(
+                action,
+                Action::BackwardSegment
+                    | Action::ForwardSegment
+                    | Action::SelectBackwardSegment
+                    | Action::SelectForwardSegment
+            );
+            let wall = prompt.horiziontal_arrow(direction, shift, ctrl);
+            return bump_for(wall, direction);
+        }
+        Action::BackwardWord | Action::ForwardWord => {
+            let direction = if action == Action::BackwardWord {
+                Direction::Left
+            } else {
+                Direction::Right
+            };
+            let to = prompt.word_in_direction(direction);
+            return bump_for(prompt.move_to(to), direction);
+        }
+        Action::BackwardDeleteChar => return bump_for(prompt.backspace(), Direction::Left),
+        Action::DeleteWordBackward => {
+            if prompt.ctrl_backspace() {
+                return Edited::Bump(50.0, Direction::Left);
+            }
+        }
+        Action::TransposeChars => return bump_for(prompt.transpose(), Direction::Right),
+        Action::UpcaseWord | Action::DowncaseWord | Action::CapitalizeWord => {
+            let case = match action {
+                Action::UpcaseWord => WordCase::Upper,
+                Action::DowncaseWord => WordCase::Lower,
+                _ => WordCase::Capitalized,
+            };
+            return bump_for(prompt.change_word_case(case), Direction::Right);
+        }
+        // on the first/last line these go through history instead
+        Action::UpLineOrHistory | Action::DownLineOrHistory => {
+            let up = action == Action::UpLineOrHistory;
+            if prompt.move_line(up) {
+                return Edited::NotAnEdit;
+            }
+            let direction = if up {
+                Direction::Left
+            } else {
+                Direction::Right
+            };
+            return Edited::Bump(10.0, direction);
+        }
+        _ => return Edited::NotAnEdit,
+    }
+    Edited::Quietly
+}
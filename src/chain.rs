@@ -1,66 +1,300 @@
-use crate::{element::ElementType, misc_types::Spring};
-use std::sync::MutexGuard;
+use crate::{
+    element::ElementType,
+    misc_types::{Direction, Spring},
+    prompt::Prompt,
+};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+// slower than this (in columns a second, or columns a second a second) and we call it stopped.
+// the springs never quite stop on their own, they just jiggle around a fraction of a column forever
+const SETTLED: f32 = 0.5;
 
 // maybe i should stop empubbinating everything
 pub struct Chain {
     pub spring: Spring,
     pub links: Vec<ChainLink>,
+    // how many rows the last frame took up & which of them the cursor was left on,
+    // so the next one knows how far up to go. anything else printing needs to keep these right
+    pub rows: u16,
+    pub cursor_row: u16,
+    // which row of the terminal the chain starts on (the renderer keeps it right when it scrolls), so
+    // mouse clicks can be lined up with it without asking the terminal
+    pub top_row: u16,
+    // set this after printing over the chain, so the renderer draws everything again instead of
+    // just what changed
+    pub repaint: bool,
+    // hanging off the right edge of the input line (like zsh's RPROMPT) & on a line of its own above
+    // it. each has its own springs
+    pub right: Option<SideChain>,
+    pub above: Option<SideChain>,
+    // how wide the terminal is, the right chain needs something to hang off of
+    pub columns: u16,
+    // how many columns of the input are scrolled off to the left because it didn't fit
+    pub scroll: u16,
+}
+
+// a chain without the prompt in it
+pub struct SideChain {
+    pub spring: Spring,
+    pub links: Vec<ChainLink>,
+    // (right chain) how many links from the left are out of the way so the input has room
+    pub hidden: usize,
+}
+
+impl SideChain {
+    // no elements, no chain
+    pub fn new(spring: Spring, elements: Vec<ElementType>) -> Option<SideChain> {
+        if elements.is_empty() {
+            return None;
+        }
+        Some(SideChain {
+            spring,
+            links: links_for(elements),
+            hidden: 0,
+        })
+    }
+
+    // the links that are actually out
+    pub fn visible(&self) -> &[ChainLink] {
+        &self.links[self.hidden..]
+    }
+
+    pub fn visible_mut(&mut self) -> &mut [ChainLink] {
+        &mut self.links[self.hidden..]
+    }
+}
+
+// which end of the terminal a chain hangs from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Anchor {
+    Left,
+    // (the column the chain's right end wants to be at)
+    Right(u16),
+}
+
+// which chain a link's in, for finding it again
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Strand {
+    Main,
+    Right,
+    Above,
 }
 
-pub fn calculate_force(chain: &Chain, link_index: usize) -> f32 {
-    let link = &chain.links[link_index];
+fn links_for(elements: impl IntoIterator<Item = ElementType>) -> Vec<ChainLink> {
+    elements
+        .into_iter()
+        .enumerate()
+        .map(|(i, e)| ChainLink {
+            mass: ChainMass {
+                // TODO)) chainmass should set itself intelligently 🧠🧠🧠 instead of being defined here...
+                position: i as f32 - 10.0,
+                mass: 1.0,
+                velocity: 0.0,
+                width: 0, // set by render function...
+                hidden: false,
+            },
+            element: e,
+        })
+        .collect()
+}
+
+impl Chain {
+    // the prompt always goes on the end
+    pub fn new(spring: Spring, elements: Vec<ElementType>, prompt: Arc<Mutex<Prompt>>) -> Chain {
+        let links = links_for(elements.into_iter().chain([ElementType::Prompt(prompt)]));
+
+        Chain {
+            spring,
+            links,
+            rows: 1,
+            cursor_row: 0,
+            top_row: 0,
+            repaint: true,
+            right: None,
+            above: None,
+            columns: 80,
+            scroll: 0,
+        }
+    }
+
+    // the row the input line (and the main & right chains) are on, the above chain gets row 0
+    pub fn input_row(&self) -> u16 {
+        self.above.is_some() as u16
+    }
+
+    pub fn links_of(&self, strand: Strand) -> &[ChainLink] {
+        match strand {
+            Strand::Main => &self.links,
+            Strand::Right => self.right.as_ref().map_or(&[], |r| r.visible()),
+            Strand::Above => self.above.as_ref().map_or(&[], |a| &a.links),
+        }
+    }
+
+    pub fn links_of_mut(&mut self, strand: Strand) -> &mut [ChainLink] {
+        match strand {
+            Strand::Main => &mut self.links,
+            Strand::Right => self.right.as_mut().map_or(&mut [], |r| r.visible_mut()),
+            Strand::Above => self.above.as_mut().map_or(&mut [], |a| &mut a.links),
+        }
+    }
+
+    // how many of the right chain's links have to get out of the way of the input. they go from
+    // the left, the one right up against the edge is the last to go
+    fn right_links_to_hide(&self) -> usize {
+        let Some(right) = &self.right else {
+            return 0;
+        };
+        let Some(prompt) = self.links.last() else {
+            return 0;
+        };
+        let input_end = prompt.mass.position.round() as i32 + prompt.mass.width as i32;
+        let mut edge = self.columns as i32 - RIGHT_MARGIN as i32;
+        for (i, link) in right.links.iter().enumerate().rev() {
+            edge -= calculate_spring_distance(right.spring.spacing, link) as i32;
+            if edge <= input_end {
+                return i + 1;
+            }
+        }
+        0
+    }
+
+    // give the prompt a shove
+    pub fn bump(&mut self, velocity: f32, direction: Direction) {
+        // TODO)) make it so we don't have to iterate through the entire chain each time we bump
+        for v in self.links.iter_mut() {
+            if let ElementType::Prompt(_) = v.element {
+                v.mass.velocity += match direction {
+                    Direction::Left => -velocity,
+                    Direction::Right => velocity,
+                };
+            }
+        }
+    }
+}
+
+// where the first link on the left sits
+pub const LEFT_MARGIN: u16 = 2;
+// the right chain keeps this many columns free at the edge of the terminal
+pub const RIGHT_MARGIN: u16 = 1;
+
+pub fn calculate_force(
+    spring: &Spring,
+    links: &[ChainLink],
+    link_index: usize,
+    anchor: Anchor,
+) -> f32 {
+    let link = &links[link_index];
     let mut force: f32 = 0.0;
 
     // left spring
     // we could DRY this out, right?
     if link_index != 0 {
-        if let Some(left_neighbour) = chain.links.get(link_index - 1) {
-            let natural_distance = calculate_spring_distance(chain.spring.spacing, left_neighbour);
+        if let Some(left_neighbour) = links.get(link_index - 1) {
+            let natural_distance = calculate_spring_distance(spring.spacing, left_neighbour);
             let displacement = link.mass.position - left_neighbour.mass.position;
-            force += -chain.spring.constant * (displacement - (natural_distance as f32));
+            force += -spring.constant * (displacement - (natural_distance as f32));
         }
-    } else {
+    } else if anchor == Anchor::Left {
         // nudge the starting element to zero, so we can anchor to something
         // will be removed when i figure out how to do this more cleanly
-        let natural_distance = 2;
+        let natural_distance = LEFT_MARGIN;
         let displacement = link.mass.position; // goal position is ZERO!
-        force += -chain.spring.constant * (displacement - (natural_distance as f32))
+        force += -spring.constant * (displacement - (natural_distance as f32))
     }
 
     // right spring
-    if let Some(right_neighbour) = chain.links.get(link_index + 1) {
-        let natural_distance = calculate_spring_distance(chain.spring.spacing, link); // ERM!!!
+    if let Some(right_neighbour) = links.get(link_index + 1) {
+        let natural_distance = calculate_spring_distance(spring.spacing, link); // ERM!!!
         let displacement = right_neighbour.mass.position - link.mass.position;
-        force += chain.spring.constant * (displacement - (natural_distance as f32));
+        force += spring.constant * (displacement - (natural_distance as f32));
+    } else if let Anchor::Right(columns) = anchor {
+        // the same thing backwards, the right end of the last link gets pulled to the edge
+        let goal = columns.saturating_sub(RIGHT_MARGIN) as f32;
+        let displacement = goal - (link.mass.position + link.mass.width as f32);
+        force += spring.constant * displacement;
     }
 
-    force -= chain.spring.dampening * link.mass.velocity;
+    force -= spring.dampening * link.mass.velocity;
 
     force
 }
 
-pub fn step_links(chain: &mut MutexGuard<Chain>, dt: f32) {
+// every chain that's out, with its springs & what it's hanging from
+fn strands(chain: &Chain) -> Vec<(&Spring, &[ChainLink], Anchor)> {
+    let mut strands = vec![(&chain.spring, chain.links.as_slice(), Anchor::Left)];
+    if let Some(right) = &chain.right {
+        strands.push((&right.spring, right.visible(), Anchor::Right(chain.columns)));
+    }
+    if let Some(above) = &chain.above {
+        strands.push((&above.spring, above.links.as_slice(), Anchor::Left));
+    }
+    strands
+}
+
+// nothing's going to move (visibly) until something gets bumped
+pub fn is_settled(chain: &Chain) -> bool {
+    let hiding = chain.right.as_ref().map_or(0, |r| r.hidden);
+    hiding == chain.right_links_to_hide()
+        && strands(chain).into_iter().all(|(spring, links, anchor)| {
+            (0..links.len()).all(|i| {
+                let link = &links[i];
+                link.mass.velocity.abs() < SETTLED
+                    && (calculate_force(spring, links, i, anchor) / link.mass.mass).abs() < SETTLED
+            })
+        })
+}
+
+fn step_strand(spring: &Spring, links: &mut [ChainLink], anchor: Anchor, dt: f32) {
     // I DONT KNOW WHAT VERLET INTEGRATION IS
-    let n = chain.links.len();
+    let n = links.len();
 
     // calculate extra forces and such
     let mut extra_forces = vec![0.0; n];
     for (i, force) in extra_forces.iter_mut().enumerate() {
-        *force = calculate_force(chain, i) / chain.links.get(i).unwrap().mass.mass;
+        *force = calculate_force(spring, links, i, anchor) / links[i].mass.mass;
     }
 
-    for (i, link) in chain.links.iter_mut().enumerate() {
+    for (i, link) in links.iter_mut().enumerate() {
         link.mass.position += link.mass.velocity * dt + 0.5 * extra_forces[i] * dt * dt;
 
         link.mass.velocity += extra_forces[i];
     }
 }
 
+pub fn step_links(chain: &mut MutexGuard<Chain>, dt: f32) {
+    // make room for the input (or give the right chain back what it had)
+    let hide = chain.right_links_to_hide();
+    let columns = chain.columns;
+    if let Some(right) = &mut chain.right
+        && right.hidden != hide
+    {
+        // whatever's hiding waits behind the first link that's still out, so it slides back out from there
+        let behind = right.links.get(hide).map(|l| l.mass.position);
+        for link in &mut right.links[..hide] {
+            link.mass.position = behind.unwrap_or(columns as f32);
+            link.mass.velocity = 0.0;
+        }
+        right.hidden = hide;
+    }
+
+    let chain = &mut **chain;
+    step_strand(&chain.spring, &mut chain.links, Anchor::Left, dt);
+    if let Some(right) = &mut chain.right {
+        let links = &mut right.links[right.hidden..];
+        step_strand(&right.spring, links, Anchor::Right(columns), dt);
+    }
+    if let Some(above) = &mut chain.above {
+        step_strand(&above.spring, &mut above.links, Anchor::Left, dt);
+    }
+}
+
 pub struct ChainMass {
     pub mass: f32,
     pub position: f32,
     pub velocity: f32,
     pub width: u16,
+    // out of the way because there wasn't room for it (also set by the render function)
+    pub hidden: bool,
 }
 
 pub struct ChainLink {
@@ -68,8 +302,10 @@ pub struct ChainLink {
     pub element: ElementType,
 }
 
-// ignoring the right element until we have to anchor shit to the right
-// also its kind of annoying that we have to define the generic despite not touching it at all
 fn calculate_spring_distance(spacing: u16, l: &ChainLink) -> u16 {
+    // (hidden ones don't get any spacing either, the links on both sides just close up)
+    if l.mass.hidden {
+        return 0;
+    }
     l.mass.width + spacing // good enough
 }
@@ -0,0 +1,47 @@
+use std::io::Write;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// not pulling in a crate for 20 lines
+pub fn base64(input: &[u8]) -> String {
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64_ALPHABET[(n >> (18 - i * 6)) as usize & 0x3F] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+// asks the terminal to put text on the system clipboard (OSC 52). terminals that don't support it
+// should just ignore this.
+pub fn set_clipboard<W: Write>(w: &mut W, text: &str) -> std::io::Result<()> {
+    write!(w, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clipboard::base64;
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64("évil".as_bytes()), "w6l2aWw=");
+    }
+}
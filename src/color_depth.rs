@@ -0,0 +1,319 @@
+use crate::error::EASHError;
+use crossterm::style::Color as ctColor;
+use std::{env, fs, path::PathBuf};
+
+// how many colors the terminal can actually show. the config is all rgb, so anything less than
+// TrueColor gets squished down to the closest thing right before it goes out
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum ColorDepth {
+    NoColor,
+    Ansi16,
+    Ansi256,
+    TrueColor,
+}
+
+// --color=never|auto|always
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ColorChoice {
+    Never,
+    Auto,
+    Always,
+}
+
+impl ColorChoice {
+    pub fn parse(s: &str) -> Result<ColorChoice, EASHError> {
+        match s {
+            "never" => Ok(ColorChoice::Never),
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            _ => Err(EASHError::InvalidArgument(format!("--color={s}"))),
+        }
+    }
+}
+
+pub fn detect(choice: ColorChoice) -> ColorDepth {
+    detect_from(choice, |name| env::var(name).ok(), terminfo_colors)
+}
+
+// the actual guessing, with the environment & terminfo passed in so it can be tested
+pub fn detect_from(
+    choice: ColorChoice,
+    var: impl Fn(&str) -> Option<String>,
+    terminfo: impl Fn(&str) -> Option<i32>,
+) -> ColorDepth {
+    let term = var("TERM").unwrap_or_default();
+    if choice == ColorChoice::Never {
+        return ColorDepth::NoColor;
+    }
+    // https://no-color.org, any value that isn't empty counts
+    if choice == ColorChoice::Auto
+        && (var("NO_COLOR").is_some_and(|v| !v.is_empty()) || term == "dumb")
+    {
+        return ColorDepth::NoColor;
+    }
+
+    let colorterm = var("COLORTERM").unwrap_or_default().to_lowercase();
+    let depth = if colorterm == "truecolor"
+        || colorterm == "24bit"
+        || term.ends_with("-direct")
+        || term.contains("truecolor")
+    {
+        ColorDepth::TrueColor
+    } else {
+        match terminfo(&term) {
+            Some(c) if c >= 1 << 24 => ColorDepth::TrueColor,
+            Some(c) if c >= 256 => ColorDepth::Ansi256,
+            Some(c) if c >= 8 => ColorDepth::Ansi16,
+            Some(_) => ColorDepth::NoColor,
+            // no terminfo to go off of, so just the name
+            None if term.contains("256color") => ColorDepth::Ansi256,
+            None => ColorDepth::Ansi16,
+        }
+    };
+
+    // always means always, even if the terminal says it can't
+    match choice {
+        ColorChoice::Always => depth.max(ColorDepth::Ansi16),
+        _ => depth,
+    }
+}
+
+// the "colors" number out of the terminal's terminfo entry, if we can find one
+pub fn terminfo_colors(term: &str) -> Option<i32> {
+    let first = term.chars().next()?;
+    let mut directories: Vec<PathBuf> = Vec::new();
+    if let Ok(dir) = env::var("TERMINFO") {
+        directories.push(dir.into());
+    }
+    if let Ok(home) = env::var("HOME") {
+        directories.push(PathBuf::from(home).join(".terminfo"));
+    }
+    for dir in [
+        "/etc/terminfo",
+        "/lib/terminfo",
+        "/usr/share/terminfo",
+        "/usr/lib/terminfo",
+    ] {
+        directories.push(dir.into());
+    }
+
+    // usually under the first letter, but macOS uses the letter's hex code
+    directories
+        .iter()
+        .flat_map(|d| {
+            [
+                d.join(first.to_string()),
+                d.join(format!("{:x}", first as u32)),
+            ]
+        })
+        .find_map(|d| fs::read(d.join(term)).ok())
+        .and_then(|entry| parse_terminfo_colors(&entry))
+}
+
+// see term(5). the header is six little endian shorts, then the names, the booleans, maybe a byte of
+// padding, then the numbers. colors is number #13
+pub fn parse_terminfo_colors(entry: &[u8]) -> Option<i32> {
+    const COLORS: usize = 13;
+    let short = |i: usize| -> Option<i32> {
+        let bytes = entry.get(i * 2..i * 2 + 2)?;
+        Some(i16::from_le_bytes([bytes[0], bytes[1]]) as i32)
+    };
+
+    // the newer format has 32 bit numbers
+    let number_size = match short(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    // (a broken entry could have negative sizes, which would wrap around as usize)
+    let size = |i: usize| usize::try_from(short(i)?).ok();
+    let names_size = size(1)?;
+    let bool_count = size(2)?;
+    let number_count = size(3)?;
+    if COLORS >= number_count {
+        return None;
+    }
+
+    let mut numbers = 12 + names_size + bool_count;
+    numbers += numbers % 2;
+    let at = numbers + COLORS * number_size;
+    let bytes = entry.get(at..at + number_size)?;
+    let colors = match number_size {
+        2 => i16::from_le_bytes([bytes[0], bytes[1]]) as i32,
+        _ => i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    };
+    (colors >= 0).then_some(colors)
+}
+
+// the 16 colors as xterm draws them by default, in the order crossterm names them
+const ANSI_16: [(ctColor, (u8, u8, u8)); 16] = [
+    (ctColor::Black, (0, 0, 0)),
+    (ctColor::DarkRed, (205, 0, 0)),
+    (ctColor::DarkGreen, (0, 205, 0)),
+    (ctColor::DarkYellow, (205, 205, 0)),
+    (ctColor::DarkBlue, (0, 0, 238)),
+    (ctColor::DarkMagenta, (205, 0, 205)),
+    (ctColor::DarkCyan, (0, 205, 205)),
+    (ctColor::Grey, (229, 229, 229)),
+    (ctColor::DarkGrey, (127, 127, 127)),
+    (ctColor::Red, (255, 0, 0)),
+    (ctColor::Green, (0, 255, 0)),
+    (ctColor::Yellow, (255, 255, 0)),
+    (ctColor::Blue, (92, 92, 255)),
+    (ctColor::Magenta, (255, 0, 255)),
+    (ctColor::Cyan, (0, 255, 255)),
+    (ctColor::White, (255, 255, 255)),
+];
+
+// the steps each channel goes through in the 6x6x6 part of the 256 colors
+const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    // eyes care about green the most & blue the least
+    let d = |a: u8, b: u8, weight: u32| (a as i32 - b as i32).pow(2) as u32 * weight;
+    d(r1, r2, 3) + d(g1, g2, 4) + d(b1, b2, 2)
+}
+
+fn nearest_cube(channel: u8) -> usize {
+    (0..CUBE.len())
+        .min_by_key(|&i| (CUBE[i] as i32 - channel as i32).abs())
+        .unwrap()
+}
+
+pub fn to_256(r: u8, g: u8, b: u8) -> u8 {
+    let (ri, gi, bi) = (nearest_cube(r), nearest_cube(g), nearest_cube(b));
+    let cube = (CUBE[ri], CUBE[gi], CUBE[bi]);
+
+    // the grays at the end go in steps of 10 from 8 to 238
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray = 8 + gray_index * 10;
+
+    if distance((gray, gray, gray), (r, g, b)) < distance(cube, (r, g, b)) {
+        232 + gray_index
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+pub fn to_16(r: u8, g: u8, b: u8) -> ctColor {
+    ANSI_16
+        .iter()
+        .min_by_key(|(_, rgb)| distance(*rgb, (r, g, b)))
+        .unwrap()
+        .0
+}
+
+impl ColorDepth {
+    // the closest thing to this color the terminal can show
+    pub fn downgrade(self, color: ctColor) -> ctColor {
+        match (self, color) {
+            (ColorDepth::NoColor, _) => ctColor::Reset,
+            (ColorDepth::Ansi256, ctColor::Rgb { r, g, b }) => ctColor::AnsiValue(to_256(r, g, b)),
+            (ColorDepth::Ansi16, ctColor::Rgb { r, g, b }) => to_16(r, g, b),
+            (ColorDepth::Ansi16, ctColor::AnsiValue(v)) if v >= 16 => {
+                let (r, g, b) = ansi_value_to_rgb(v);
+                to_16(r, g, b)
+            }
+            _ => color,
+        }
+    }
+}
+
+// what one of the 256 colors (past the first 16) looks like
+fn ansi_value_to_rgb(v: u8) -> (u8, u8, u8) {
+    if v >= 232 {
+        let gray = 8 + (v - 232) * 10;
+        return (gray, gray, gray);
+    }
+    let v = (v - 16) as usize;
+    (CUBE[v / 36], CUBE[v / 6 % 6], CUBE[v % 6])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color_depth::{
+        ColorChoice, ColorDepth, detect_from, parse_terminfo_colors, to_16, to_256,
+    };
+    use crossterm::style::Color as ctColor;
+
+    fn detect(choice: ColorChoice, vars: &[(&str, &str)], colors: Option<i32>) -> ColorDepth {
+        let var = |name: &str| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, v)| v.to_string())
+        };
+        detect_from(choice, var, |_| colors)
+    }
+
+    #[test]
+    fn detection() {
+        use ColorChoice::*;
+        use ColorDepth::*;
+
+        let truecolor = [("TERM", "xterm-256color"), ("COLORTERM", "truecolor")];
+        assert_eq!(detect(Auto, &truecolor, Some(256)), TrueColor);
+        assert_eq!(
+            detect(Auto, &[("TERM", "xterm-256color")], Some(256)),
+            Ansi256
+        );
+        assert_eq!(detect(Auto, &[("TERM", "xterm-256color")], None), Ansi256);
+        assert_eq!(detect(Auto, &[("TERM", "xterm")], Some(8)), Ansi16);
+        assert_eq!(detect(Auto, &[("TERM", "xterm-direct")], None), TrueColor);
+        assert_eq!(detect(Auto, &[("TERM", "dumb")], None), NoColor);
+        assert_eq!(detect(Never, &truecolor, Some(256)), NoColor);
+
+        let no_color = [("TERM", "xterm-256color"), ("NO_COLOR", "1")];
+        assert_eq!(detect(Auto, &no_color, Some(256)), NoColor);
+        assert_eq!(detect(Always, &no_color, Some(256)), Ansi256);
+        assert_eq!(
+            detect(Auto, &[("TERM", "xterm"), ("NO_COLOR", "")], Some(8)),
+            Ansi16
+        );
+        assert_eq!(detect(Always, &[("TERM", "dumb")], None), Ansi16);
+    }
+
+    #[test]
+    fn terminfo_numbers() {
+        // legacy format: "x|y" (4 bytes of names), 1 boolean, then the padding byte & 14 numbers
+        let mut entry: Vec<u8> = [0o432i16, 4, 1, 14, 0, 0]
+            .iter()
+            .flat_map(|s| s.to_le_bytes())
+            .collect();
+        entry.extend(b"x|y\0");
+        entry.extend([1, 0]);
+        for i in 0..14i16 {
+            entry.extend(if i == 13 { 256i16 } else { -1 }.to_le_bytes());
+        }
+        assert_eq!(parse_terminfo_colors(&entry), Some(256));
+        assert_eq!(parse_terminfo_colors(&entry[..20]), None);
+        assert_eq!(parse_terminfo_colors(b"not terminfo at all"), None);
+
+        // a negative names size
+        let mut broken = entry.clone();
+        broken[2..4].copy_from_slice(&(-4i16).to_le_bytes());
+        assert_eq!(parse_terminfo_colors(&broken), None);
+    }
+
+    #[test]
+    fn quantizing() {
+        assert_eq!(to_256(255, 0, 0), 196);
+        assert_eq!(to_256(0, 0, 0), 16);
+        assert_eq!(to_256(128, 128, 128), 244);
+        assert_eq!(to_256(10, 90, 100), 23);
+        assert_eq!(to_256(56, 90, 90), 239);
+        assert_eq!(to_16(250, 10, 10), ctColor::Red);
+        assert_eq!(to_16(0, 200, 0), ctColor::DarkGreen);
+        assert_eq!(to_16(120, 120, 120), ctColor::DarkGrey);
+
+        let gray = ctColor::Rgb {
+            r: 229,
+            g: 229,
+            b: 229,
+        };
+        assert_eq!(ColorDepth::NoColor.downgrade(gray), ctColor::Reset);
+        assert_eq!(ColorDepth::Ansi16.downgrade(gray), ctColor::Grey);
+        assert_eq!(ColorDepth::TrueColor.downgrade(gray), gray);
+        assert_eq!(ColorDepth::Ansi16.downgrade(ctColor::Reset), ctColor::Reset);
+    }
+}
@@ -0,0 +1,709 @@
+use crate::evaluate::{Token, TokenType};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+// what (if anything) should be offered for a plain argument
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum ArgumentType {
+    Nothing,
+    #[default]
+    Files,
+    Directories,
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct FlagSpec {
+    pub short: Option<char>,
+    pub long: Option<String>,
+    pub old: Option<String>, // single dash long options, like -name in find
+    pub description: Option<String>,
+    pub requires_argument: bool,
+    pub values: Vec<String>, // things that can be passed to this flag
+}
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct CompletionSpec {
+    pub description: Option<String>,
+    pub subcommands: BTreeMap<String, CompletionSpec>,
+    pub flags: Vec<FlagSpec>,
+    pub arguments: ArgumentType,
+    pub values: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct CompletionConfig {
+    // folders with fish completion files in them, like /usr/share/fish/completions
+    pub fish_paths: Vec<String>,
+    pub commands: HashMap<String, CompletionSpec>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub text: String,
+    pub description: Option<String>,
+}
+
+// start & end are byte positions in the prompt that the candidates should replace
+#[derive(Debug)]
+pub struct CompletionResult {
+    pub start: usize,
+    pub end: usize,
+    pub candidates: Vec<Completion>,
+}
+
+impl CompletionResult {
+    // the longest thing we can insert without picking a candidate for the user
+    pub fn common_prefix(&self) -> String {
+        let Some(first) = self.candidates.first() else {
+            return String::new();
+        };
+
+        let mut prefix = first.text.as_str();
+        for c in self.candidates.iter().skip(1) {
+            let shared = prefix
+                .char_indices()
+                .zip(c.text.chars())
+                .find(|((_, a), b)| a != b)
+                .map(|((i, _), _)| i)
+                .unwrap_or(prefix.len().min(c.text.len()));
+            prefix = &prefix[..shared];
+        }
+
+        prefix.to_string()
+    }
+
+    // the candidates with their descriptions, for the Completions element. a lone candidate has
+    // already been typed in, so it only gets listed when there's something to say about it
+    pub fn describe(&self) -> Option<String> {
+        let listed = |c: &Completion| match &c.description {
+            Some(d) => format!("{} ({})", c.text, d),
+            None => c.text.clone(),
+        };
+        match self.candidates.as_slice() {
+            []
+            | [
+                Completion {
+                    description: None, ..
+                },
+            ] => None,
+            candidates => Some(candidates.iter().map(listed).collect::<Vec<_>>().join("  ")),
+        }
+    }
+}
+
+pub struct Completer {
+    commands: HashMap<String, CompletionSpec>,
+    fish_paths: Vec<String>,
+    // commands we already went looking for in fish_paths, so we don't hit the disk every tab
+    searched: HashSet<String>,
+}
+
+impl Completer {
+    pub fn new(config: CompletionConfig) -> Self {
+        Self {
+            commands: config.commands,
+            fish_paths: config.fish_paths,
+            searched: HashSet::new(),
+        }
+    }
+
+    // get the spec for a command, loading it out of a fish file the first time if we have to
+    fn spec_for(&mut self, command: &str) -> Option<&CompletionSpec> {
+        let name = Path::new(command)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(command)
+            .to_string();
+
+        if !self.commands.contains_key(&name) && self.searched.insert(name.clone()) {
+            for dir in self.fish_paths.iter() {
+                let path = Path::new(dir).join(format!("{}.fish", name));
+                let Ok(contents) = fs::read_to_string(path) else {
+                    continue;
+                };
+                for (command, spec) in parse_fish_completions(&contents) {
+                    self.commands.entry(command).or_insert(spec);
+                }
+                break;
+            }
+        }
+
+        self.commands.get(&name)
+    }
+
+    // tokens are the whole line's, straight from the prompt
+    pub fn complete(
+        &mut self,
+        line: &str,
+        tokens: &[Token],
+        cursor: usize,
+    ) -> Option<CompletionResult> {
+        let before = line.get(..cursor)?;
+        let word_start = before
+            .rfind(|c: char| c.is_whitespace() || c == '|' || c == '&')
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let word = &before[word_start..];
+
+        // only care about the command we're currently in, not whatever was piped into it
+        let previous: Vec<&Token> = tokens.iter().take_while(|t| t.end <= word_start).collect();
+        let segment_start = previous
+            .iter()
+            .rposition(|t| matches!(t.contents, TokenType::Pipe | TokenType::AndThen))
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        let previous = &previous[segment_start..];
+
+        // no command yet, so nothing to complete for
+        let command = previous.first()?.contents.not_a_symbol()?.clone();
+
+        let current_is_flag = !word.is_empty()
+            && matches!(
+                tokens
+                    .iter()
+                    .rfind(|t| t.start < cursor)
+                    .map(|t| &t.contents),
+                Some(TokenType::Flag(_))
+            );
+
+        let root = self.spec_for(&command)?;
+
+        // walk down through any subcommands that were already typed, collecting flags on the way
+        let mut spec = root;
+        let mut flags: Vec<&FlagSpec> = root.flags.iter().collect();
+        let mut pending_flag: Option<&FlagSpec> = None;
+        for t in previous.iter().skip(1) {
+            match &t.contents {
+                TokenType::Flag(f) => {
+                    pending_flag = find_flag(&flags, f).filter(|f| f.requires_argument);
+                }
+                other => {
+                    let Some(value) = other.not_a_symbol() else {
+                        continue;
+                    };
+                    if pending_flag.take().is_some() {
+                        continue;
+                    }
+                    if let Some(sub) = spec.subcommands.get(value) {
+                        spec = sub;
+                        flags.extend(sub.flags.iter());
+                    }
+                }
+            }
+        }
+
+        let mut candidates = Vec::new();
+        if current_is_flag {
+            let double = word.starts_with("--");
+            for f in flags.iter() {
+                if !double {
+                    if let Some(s) = f.short {
+                        candidates.push(flag_completion(format!("-{}", s), f));
+                    }
+                    if let Some(o) = &f.old {
+                        candidates.push(flag_completion(format!("-{}", o), f));
+                    }
+                }
+                if let Some(l) = &f.long {
+                    candidates.push(flag_completion(format!("--{}", l), f));
+                }
+            }
+        } else if let Some(f) = pending_flag {
+            // the previous flag wants something after it
+            candidates.extend(f.values.iter().map(|v| Completion {
+                text: v.clone(),
+                description: None,
+            }));
+            if f.values.is_empty() {
+                candidates.extend(complete_path(word, false));
+            }
+        } else {
+            candidates.extend(spec.subcommands.iter().map(|(name, sub)| Completion {
+                text: name.clone(),
+                description: sub.description.clone(),
+            }));
+            candidates.extend(spec.values.iter().map(|v| Completion {
+                text: v.clone(),
+                description: None,
+            }));
+            match spec.arguments {
+                ArgumentType::Nothing => {}
+                ArgumentType::Files => candidates.extend(complete_path(word, false)),
+                ArgumentType::Directories => candidates.extend(complete_path(word, true)),
+            }
+        }
+
+        candidates.retain(|c| c.text.starts_with(word));
+        // (the same thing can come from more than one place, the first one wins)
+        let mut seen = HashSet::new();
+        candidates.retain(|c| seen.insert(c.text.clone()));
+
+        Some(CompletionResult {
+            start: word_start,
+            end: cursor,
+            candidates,
+        })
+    }
+}
+
+fn flag_completion(text: String, flag: &FlagSpec) -> Completion {
+    Completion {
+        text,
+        description: flag.description.clone(),
+    }
+}
+
+// the tokenizer strips the dashes off, so check every spelling of the flag
+fn find_flag<'a>(flags: &[&'a FlagSpec], name: &str) -> Option<&'a FlagSpec> {
+    flags.iter().copied().find(|f| {
+        f.long.as_deref() == Some(name)
+            || f.old.as_deref() == Some(name)
+            || (name.chars().count() == 1 && f.short == name.chars().next())
+    })
+}
+
+pub fn complete_path(word: &str, directories_only: bool) -> Vec<Completion> {
+    let (dir, file_prefix) = match word.rfind('/') {
+        Some(i) => (&word[..i + 1], &word[i + 1..]),
+        None => ("", word),
+    };
+
+    // we still want to show the user ~, just not give it to read_dir
+    let search_dir = if dir.is_empty() {
+        ".".to_string()
+    } else if let Some(rest) = dir.strip_prefix('~') {
+        format!("{}{}", std::env::var("HOME").unwrap_or_default(), rest)
+    } else {
+        dir.to_string()
+    };
+
+    let Ok(entries) = fs::read_dir(search_dir) else {
+        return Vec::new();
+    };
+
+    let mut out: Vec<Completion> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            if !name.starts_with(file_prefix)
+                || (name.starts_with('.') && !file_prefix.starts_with('.'))
+            {
+                return None;
+            }
+            let is_dir = e.path().is_dir();
+            if directories_only && !is_dir {
+                return None;
+            }
+            let slash = if is_dir { "/" } else { "" };
+            Some(Completion {
+                text: format!("{}{}{}", dir, name, slash),
+                description: None,
+            })
+        })
+        .collect();
+    out.sort_by(|a, b| a.text.cmp(&b.text));
+    out
+}
+
+// splits a line of fish script into words, dealing with quotes, escapes & comments.
+// nowhere near a real fish parser but completion files are pretty tame.
+fn split_fish_words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(n) = chars.next() {
+                    current.push(match n {
+                        't' if quote.is_none() => '\t',
+                        n => n,
+                    });
+                }
+            }
+            (Some('\''), '\\') if matches!(chars.peek(), Some('\'') | Some('\\')) => {
+                current.push(chars.next().unwrap());
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, '#') if !in_word => break,
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(current);
+    }
+
+    words
+}
+
+// one `complete` line, after the options have been picked apart
+#[derive(Default)]
+struct FishLine {
+    commands: Vec<String>,
+    flag: FlagSpec,
+    is_flag: bool,
+    description: Option<String>,
+    arguments: Vec<String>,
+    condition: Option<String>,
+    no_files: bool,
+    force_files: bool,
+}
+
+fn parse_fish_line(words: &[String]) -> Option<FishLine> {
+    let mut line = FishLine::default();
+    let mut iter = words.iter().skip(1);
+
+    while let Some(word) = iter.next() {
+        // --long=value, --long value
+        if let Some(long) = word.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((n, v)) => (n, Some(v.to_string())),
+                None => (long, None),
+            };
+            let mut value = || inline.clone().or_else(|| iter.next().cloned());
+            match name {
+                "command" => line.commands.push(value()?),
+                "path" | "wraps" => _ = value(),
+                "short-option" => line.flag.short = value()?.chars().next(),
+                "long-option" => line.flag.long = value(),
+                "old-option" => line.flag.old = value(),
+                "description" => line.description = value(),
+                "arguments" => line.arguments.push(value()?),
+                "condition" => line.condition = value(),
+                "no-files" => line.no_files = true,
+                "force-files" => line.force_files = true,
+                "require-parameter" => line.flag.requires_argument = true,
+                "exclusive" => {
+                    line.no_files = true;
+                    line.flag.requires_argument = true;
+                }
+                // erasing completions isn't something we need to care about
+                "erase" => return None,
+                _ => {}
+            }
+            continue;
+        }
+
+        // -c cmd, -ccmd, and clusters like -xa 'stuff'
+        let Some(cluster) = word.strip_prefix('-') else {
+            continue;
+        };
+        for (i, opt) in cluster.char_indices() {
+            let rest = &cluster[i + opt.len_utf8()..];
+            let takes_value = matches!(opt, 'c' | 'p' | 's' | 'l' | 'o' | 'd' | 'a' | 'n' | 'w');
+            if !takes_value {
+                match opt {
+                    'f' => line.no_files = true,
+                    'F' => line.force_files = true,
+                    'r' => line.flag.requires_argument = true,
+                    'x' => {
+                        line.no_files = true;
+                        line.flag.requires_argument = true;
+                    }
+                    'e' => return None,
+                    _ => {}
+                }
+                continue;
+            }
+
+            let value = if rest.is_empty() {
+                iter.next()?.clone()
+            } else {
+                rest.to_string()
+            };
+            match opt {
+                'c' => line.commands.push(value),
+                's' => line.flag.short = value.chars().next(),
+                'l' => line.flag.long = Some(value),
+                'o' => line.flag.old = Some(value),
+                'd' => line.description = Some(value),
+                'a' => line.arguments.push(value),
+                'n' => line.condition = Some(value),
+                _ => {}
+            }
+            break;
+        }
+    }
+
+    line.is_flag = line.flag.short.is_some() || line.flag.long.is_some() || line.flag.old.is_some();
+    line.flag.description = line.description.clone();
+    Some(line)
+}
+
+// turns the contents of -a into candidates. anything that needs fish to run code is skipped.
+fn fish_arguments(arguments: &[String]) -> Vec<(String, Option<String>)> {
+    let mut out = Vec::new();
+    for a in arguments {
+        if a.contains('(') || a.contains('$') {
+            continue;
+        }
+        for w in split_fish_words(a) {
+            match w.split_once('\t') {
+                Some((v, d)) => out.push((v.to_string(), Some(d.to_string()))),
+                None => out.push((w, None)),
+            }
+        }
+    }
+    out
+}
+
+// figure out which subcommands (if any) a fish condition is talking about
+fn fish_condition_subcommands(condition: &str) -> Vec<String> {
+    if condition.trim_start().starts_with("not ") {
+        return Vec::new();
+    }
+
+    let words = split_fish_words(condition);
+    let Some(pos) = words
+        .iter()
+        .position(|w| w == "__fish_seen_subcommand_from")
+    else {
+        return Vec::new();
+    };
+
+    words[pos + 1..]
+        .iter()
+        .take_while(|w| !w.starts_with(';') && !w.starts_with('&') && *w != "and" && *w != "or")
+        .map(|w| w.trim_end_matches(';').to_string())
+        .collect()
+}
+
+fn apply_fish_line(
+    target: &mut CompletionSpec,
+    line: &FishLine,
+    arguments: &[(String, Option<String>)],
+    wants_subcommand: bool,
+) {
+    if line.is_flag {
+        let mut flag = line.flag.clone();
+        flag.values = arguments.iter().map(|(v, _)| v.clone()).collect();
+        if !flag.values.is_empty() {
+            flag.requires_argument = true;
+        }
+        target.flags.push(flag);
+        return;
+    }
+
+    if wants_subcommand {
+        for (name, description) in arguments.iter() {
+            let sub = target.subcommands.entry(name.clone()).or_default();
+            sub.description = description.clone().or_else(|| line.description.clone());
+        }
+    } else {
+        target
+            .values
+            .extend(arguments.iter().map(|(v, _)| v.clone()));
+    }
+
+    if line.no_files {
+        target.arguments = ArgumentType::Nothing;
+    } else if line.force_files {
+        target.arguments = ArgumentType::Files;
+    }
+}
+
+// reads `complete -c ...` lines out of a fish completion file.
+// only understands the subset of fish that basically every completion file uses, conditions are
+// boiled down to "top level" or "after one of these subcommands".
+pub fn parse_fish_completions(contents: &str) -> HashMap<String, CompletionSpec> {
+    let mut specs: HashMap<String, CompletionSpec> = HashMap::new();
+
+    for raw in contents.lines() {
+        let words = split_fish_words(raw);
+        if words.first().map(|w| w.as_str()) != Some("complete") {
+            continue;
+        }
+        let Some(line) = parse_fish_line(&words) else {
+            continue;
+        };
+
+        let subcommands = line
+            .condition
+            .as_deref()
+            .map(fish_condition_subcommands)
+            .unwrap_or_default();
+        let wants_subcommand = line
+            .condition
+            .as_deref()
+            .is_some_and(|c| c.contains("__fish_use_subcommand"));
+        let arguments = fish_arguments(&line.arguments);
+
+        for command in line.commands.iter() {
+            let root = specs.entry(command.clone()).or_default();
+            if subcommands.is_empty() {
+                apply_fish_line(root, &line, &arguments, wants_subcommand);
+                continue;
+            }
+
+            // subcommands can get completions before they get declared, so only make new ones
+            // if none of the ones mentioned exist yet
+            let mut names: Vec<&String> = subcommands
+                .iter()
+                .filter(|n| root.subcommands.contains_key(*n))
+                .collect();
+            if names.is_empty() {
+                names = subcommands.iter().collect();
+            }
+            for name in names {
+                let target = root.subcommands.entry(name.clone()).or_default();
+                apply_fish_line(target, &line, &arguments, wants_subcommand);
+            }
+        }
+    }
+
+    specs
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::completion::{
+        ArgumentType, Completer, CompletionConfig, CompletionSpec, FlagSpec, parse_fish_completions,
+    };
+    use crate::evaluate::tokenize;
+    use std::collections::HashMap;
+
+    #[test]
+    fn fish_subcommands_and_flags() {
+        let file = r#"
+# a comment, should be ignored
+complete -c tool -f
+complete -c tool -n '__fish_use_subcommand' -a build -d 'Build the thing'
+complete -c tool -n "__fish_use_subcommand" -xa 'run clean\t"Remove junk"'
+complete -c tool -s v -l verbose -d 'Be loud'
+complete -c tool -n '__fish_seen_subcommand_from build' -l release -d "Optimize it"
+complete -c tool -n '__fish_seen_subcommand_from run' -l target -r -a '(some_command)'
+"#;
+        let specs = parse_fish_completions(file);
+        let tool = &specs["tool"];
+
+        assert_eq!(tool.arguments, ArgumentType::Nothing);
+        assert_eq!(
+            tool.subcommands.keys().collect::<Vec<_>>(),
+            vec!["build", "clean", "run"]
+        );
+        assert_eq!(
+            tool.subcommands["build"].description.as_deref(),
+            Some("Build the thing")
+        );
+        assert_eq!(
+            tool.subcommands["clean"].description.as_deref(),
+            Some("Remove junk")
+        );
+        assert_eq!(tool.flags[0].short, Some('v'));
+        assert_eq!(tool.flags[0].long.as_deref(), Some("verbose"));
+        assert_eq!(
+            tool.subcommands["build"].flags[0].long.as_deref(),
+            Some("release")
+        );
+        assert!(tool.subcommands["run"].flags[0].requires_argument);
+        assert!(tool.subcommands["run"].flags[0].values.is_empty());
+    }
+
+    #[test]
+    fn the_same_candidate_only_shows_up_once() {
+        let mut spec = CompletionSpec {
+            arguments: ArgumentType::Nothing,
+            values: vec!["show".to_string()],
+            ..Default::default()
+        };
+        spec.subcommands
+            .insert("show".to_string(), CompletionSpec::default());
+        spec.subcommands
+            .insert("stash".to_string(), CompletionSpec::default());
+
+        let mut commands = HashMap::new();
+        commands.insert("thing".to_string(), spec);
+        let mut completer = Completer::new(CompletionConfig {
+            fish_paths: Vec::new(),
+            commands,
+        });
+
+        let line = "thing s";
+        let result = completer
+            .complete(line, &tokenize(line), line.len())
+            .unwrap();
+        let texts: Vec<&str> = result.candidates.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["show", "stash"]);
+    }
+
+    #[test]
+    fn complete_flags_after_dash() {
+        let mut spec = CompletionSpec {
+            arguments: ArgumentType::Nothing,
+            ..Default::default()
+        };
+        spec.flags.push(FlagSpec {
+            short: Some('a'),
+            long: Some("all".to_string()),
+            description: Some("everything".to_string()),
+            ..Default::default()
+        });
+        spec.subcommands.insert(
+            "show".to_string(),
+            CompletionSpec {
+                flags: vec![FlagSpec {
+                    long: Some("stat".to_string()),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        );
+
+        let mut commands = HashMap::new();
+        commands.insert("thing".to_string(), spec);
+        let mut completer = Completer::new(CompletionConfig {
+            fish_paths: Vec::new(),
+            commands,
+        });
+
+        let line = "echo hi | thing show --";
+        let result = completer
+            .complete(line, &tokenize(line), line.len())
+            .unwrap();
+        let texts: Vec<&str> = result.candidates.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["--all", "--stat"]);
+        assert_eq!(result.start, line.len() - 2);
+
+        let line = "thing -";
+        let result = completer
+            .complete(line, &tokenize(line), line.len())
+            .unwrap();
+        let texts: Vec<&str> = result.candidates.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["-a", "--all"]);
+        assert_eq!(
+            result.describe().as_deref(),
+            Some("-a (everything)  --all (everything)")
+        );
+
+        let line = "thing s";
+        let result = completer
+            .complete(line, &tokenize(line), line.len())
+            .unwrap();
+        assert_eq!(result.common_prefix(), "show");
+        assert_eq!(result.describe(), None);
+    }
+}
@@ -1,22 +1,82 @@
 use crate::{
-    element::{BasicElement, ElementType},
+    completion::CompletionConfig,
+    element::{BasicElement, ElementType, ModeIndicator, PromptUpdate, ReactiveElement},
     error::EASHError,
-    misc_types::{Alignment, Color, Glyph, HexColor, Spring, VisualState, Width},
+    evaluate::current_command,
+    gradient::{Coverage, Gradient, Interpolation},
+    keymap::Action,
+    misc_types::{
+        Alignment, Cap, Color, EASHPallete, Glyph, HexColor, Overflow, Spring, VisualState, Width,
+    },
+    prompt::Prompt,
+    vi::ViConfig,
 };
 use serde::{
     Deserialize,
     de::{Error as ValueError, Visitor},
 };
-use std::{collections::HashMap, fs, str::FromStr, time::Instant};
+use std::{
+    collections::HashMap,
+    fs,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
 #[derive(Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub chain_elements: Vec<ConfigElement>,
+    // hanging off the right edge of the input line, and on their own line above it
+    #[serde(default)]
+    pub right_elements: Vec<ConfigElement>,
+    #[serde(default)]
+    pub above_elements: Vec<ConfigElement>,
     #[serde(default)]
     pub glyphs: GlyphList,
     #[serde(default)]
     pub spring: SpringConfig,
+    // (these two default to spring)
+    pub right_spring: Option<SpringConfig>,
+    pub above_spring: Option<SpringConfig>,
+    #[serde(default)]
+    pub completions: CompletionConfig,
+    #[serde(default)]
+    pub colors: ConfigPallete,
+    #[serde(default)]
+    pub vi: ViConfig,
+    // "ctrl-x ctrl-e" = "action-name", on top of keymap::DEFAULT_KEYBINDINGS
+    #[serde(default)]
+    pub keybindings: HashMap<String, String>,
+    #[serde(default)]
+    pub editing: EditingConfig,
+    // "gco" = "git checkout"
+    #[serde(default)]
+    pub abbreviations: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct EditingConfig {
+    // closing quotes & brackets for you
+    pub auto_pair: bool,
+    // how hard a paste thuds into the chain
+    pub paste_bump: f32,
+    // clicking & dragging in the prompt. turn it off to get your terminal's own selection back
+    pub mouse: bool,
+    // accept the line straight away after editing it in $EDITOR, instead of putting it back in the prompt
+    pub execute_after_edit: bool,
+}
+
+impl Default for EditingConfig {
+    fn default() -> Self {
+        Self {
+            auto_pair: true,
+            paste_bump: 40.0,
+            mouse: false,
+            execute_after_edit: false,
+        }
+    }
 }
 
 // clone trait of shame...
@@ -159,16 +219,61 @@ impl<'de> Deserialize<'de> for GlyphList {
     }
 }
 
+// only ever lives long enough to get turned into an ElementType, so the size doesn't matter
+#[allow(clippy::large_enum_variant)]
 #[derive(Deserialize, Clone)]
 #[serde(tag = "type")]
 pub enum ConfigElement {
     BasicElement {
         content: String,
         visual_state: ConfigVisualState,
+        // name of a keymap action to run when it's clicked
+        #[serde(default)]
+        on_click: Option<String>,
+    },
+    ModeIndicator {
+        insert: ConfigModeLook,
+        normal: ConfigModeLook,
+        visual: ConfigModeLook,
+        #[serde(default)]
+        on_click: Option<String>,
+    },
+    // shows the command that's being typed, or empty when there isn't one
+    Command {
+        #[serde(default)]
+        empty: String,
+        visual_state: ConfigVisualState,
+        #[serde(default)]
+        on_click: Option<String>,
+    },
+    // lists what the last completion offered & their descriptions, until the text changes
+    Completions {
+        visual_state: ConfigVisualState,
+        #[serde(default)]
+        on_click: Option<String>,
     },
     Prompt,
 }
 
+// what a mode indicator looks like in one mode
+#[derive(Deserialize, Clone)]
+pub struct ConfigModeLook {
+    pub content: String,
+    pub visual_state: ConfigVisualState,
+}
+
+impl TryFrom<ConfigModeLook> for BasicElement {
+    type Error = EASHError;
+
+    fn try_from(value: ConfigModeLook) -> Result<Self, Self::Error> {
+        Ok(BasicElement {
+            content: value.content,
+            visual_state: value.visual_state.try_into()?,
+            on_click: None,
+        })
+    }
+}
+
 #[derive(Deserialize, Clone)]
 pub struct ConfigVisualState {
     pub align: String,
@@ -176,6 +281,16 @@ pub struct ConfigVisualState {
     pub padding: u32,
     pub bg_color: ConfigColor,
     pub color: ConfigColor,
+    // "Powerline", "Rounded", "Slant" or any glyph you want
+    #[serde(default)]
+    pub left_cap: Option<String>,
+    #[serde(default)]
+    pub right_cap: Option<String>,
+    // "Clip" (default), "Ellipsis", "Hide" or "Shrink", for when the chain doesn't fit
+    #[serde(default)]
+    pub overflow: Option<String>,
+    #[serde(default)]
+    pub priority: i32,
 }
 
 #[derive(Deserialize, Clone)]
@@ -183,16 +298,125 @@ pub struct ConfigVisualState {
 pub enum ConfigColor {
     Transparent,
     Solid { r: u8, g: u8, b: u8 },
-    Gradient { from: HexColor, to: HexColor },
+    // (boxed so every solid color doesn't have to be gradient sized)
+    Gradient(Box<ConfigGradient>),
 }
 
-impl From<ConfigColor> for Color {
-    fn from(value: ConfigColor) -> Self {
+// either from & to, or a list of stops (positions are optional, 0 to 1)
+#[derive(Deserialize, Clone)]
+pub struct ConfigGradient {
+    pub from: Option<HexColor>,
+    pub to: Option<HexColor>,
+    #[serde(default)]
+    pub stops: Vec<ConfigGradientStop>,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    #[serde(default)]
+    pub cover: Coverage,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct ConfigGradientStop {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub position: Option<f32>,
+}
+
+impl TryFrom<ConfigColor> for Color {
+    type Error = EASHError;
+
+    fn try_from(value: ConfigColor) -> Result<Self, Self::Error> {
         match value {
-            ConfigColor::Transparent => Color::Transparent,
-            ConfigColor::Solid { r, g, b } => Color::Solid(HexColor { r, g, b }),
-            ConfigColor::Gradient { from, to } => Color::Gradient(from, to),
+            ConfigColor::Transparent => Ok(Color::Transparent),
+            ConfigColor::Solid { r, g, b } => Ok(Color::Solid(HexColor { r, g, b })),
+            ConfigColor::Gradient(gradient) => {
+                let ConfigGradient {
+                    from,
+                    to,
+                    stops,
+                    interpolation,
+                    cover,
+                } = *gradient;
+                let mut all: Vec<(HexColor, Option<f32>)> =
+                    from.map(|c| (c, None)).into_iter().collect();
+                for stop in stops {
+                    // (Gradient::new clamps each stop between the last one & 1, a NaN in there panics)
+                    if stop.position.is_some_and(f32::is_nan) {
+                        return Err(EASHError::ConfigInvalidType {
+                            expected: "gradient stop position from 0 to 1",
+                            got: "nan".to_string(),
+                        });
+                    }
+                    let color = HexColor {
+                        r: stop.r,
+                        g: stop.g,
+                        b: stop.b,
+                    };
+                    all.push((color, stop.position));
+                }
+                all.extend(to.map(|c| (c, None)));
+
+                if all.len() < 2 {
+                    return Err(EASHError::ConfigInvalidType {
+                        expected: "gradient with at least two colors",
+                        got: format!("{} color(s)", all.len()),
+                    });
+                }
+                Ok(Color::Gradient(Box::new(Gradient::new(
+                    all,
+                    interpolation,
+                    cover,
+                ))))
+            }
+        }
+    }
+}
+
+// anything left out keeps the default from EASHPallete
+#[derive(Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ConfigPallete {
+    pub value_fg: Option<ConfigColor>,
+    pub string_fg: Option<ConfigColor>,
+    pub directory_fg: Option<ConfigColor>,
+    pub flag_fg: Option<ConfigColor>,
+    pub andthen_fg: Option<ConfigColor>,
+    pub pipe_fg: Option<ConfigColor>,
+    pub selection_fg: Option<ConfigColor>,
+    pub selection_bg: Option<ConfigColor>,
+    pub bracket_match_bg: Option<ConfigColor>,
+    pub bracket_unbalanced_fg: Option<ConfigColor>,
+    pub abbreviation_bg: Option<ConfigColor>,
+}
+
+impl TryFrom<ConfigPallete> for EASHPallete {
+    type Error = EASHError;
+
+    fn try_from(value: ConfigPallete) -> Result<Self, Self::Error> {
+        let mut pallete = EASHPallete::default();
+        let fields = [
+            (value.value_fg, &mut pallete.value_fg),
+            (value.string_fg, &mut pallete.string_fg),
+            (value.directory_fg, &mut pallete.directory_fg),
+            (value.flag_fg, &mut pallete.flag_fg),
+            (value.andthen_fg, &mut pallete.andthen_fg),
+            (value.pipe_fg, &mut pallete.pipe_fg),
+            (value.selection_fg, &mut pallete.selection_fg),
+            (value.selection_bg, &mut pallete.selection_bg),
+            (value.bracket_match_bg, &mut pallete.bracket_match_bg),
+            (
+                value.bracket_unbalanced_fg,
+                &mut pallete.bracket_unbalanced_fg,
+            ),
+            (value.abbreviation_bg, &mut pallete.abbreviation_bg),
+        ];
+        for (config, color) in fields {
+            if let Some(c) = config {
+                *color = c.try_into()?;
+            }
         }
+        Ok(pallete)
     }
 }
 
@@ -219,6 +443,37 @@ impl FromStr for Alignment {
     }
 }
 
+impl FromStr for Cap {
+    type Err = EASHError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "none" => Ok(Cap::None),
+            "powerline" => Ok(Cap::Powerline),
+            "rounded" => Ok(Cap::Rounded),
+            "slant" => Ok(Cap::Slant),
+            _ => Ok(Cap::Custom(s.to_string())),
+        }
+    }
+}
+
+impl FromStr for Overflow {
+    type Err = EASHError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clip" => Ok(Overflow::Clip),
+            "ellipsis" => Ok(Overflow::Ellipsis),
+            "hide" => Ok(Overflow::Hide),
+            "shrink" => Ok(Overflow::Shrink),
+            _ => Err(Self::Err::ConfigInvalidType {
+                expected: "Overflow",
+                got: s.to_string(),
+            }),
+        }
+    }
+}
+
 fn bracket_match(s: &str) -> Result<Option<(String, String)>, EASHError> {
     let s = s.trim();
     let Some(open_paren_pos) = s.find('(') else {
@@ -276,8 +531,12 @@ impl TryFrom<ConfigVisualState> for VisualState {
             align: Alignment::from_str(&value.align)?,
             width: Width::from_str(&value.width)?,
             padding: value.padding,
-            bg_color: value.bg_color.into(),
-            color: value.color.into(),
+            bg_color: value.bg_color.try_into()?,
+            color: value.color.try_into()?,
+            left_cap: Cap::from_str(value.left_cap.as_deref().unwrap_or(""))?,
+            right_cap: Cap::from_str(value.right_cap.as_deref().unwrap_or(""))?,
+            overflow: Overflow::from_str(value.overflow.as_deref().unwrap_or("clip"))?,
+            priority: value.priority,
         })
     }
 }
@@ -315,21 +574,85 @@ pub fn find_config() -> Result<Option<String>, EASHError> {
     Ok(None)
 }
 
-pub fn get_elements_from_config(config: &Config) -> Result<Vec<ElementType>, EASHError> {
+fn click_action(name: &Option<String>) -> Result<Option<Action>, EASHError> {
+    let Some(name) = name else {
+        return Ok(None);
+    };
+    Action::from_name(name)
+        .map(Some)
+        .ok_or_else(|| EASHError::ConfigInvalidAction(name.clone()))
+}
+
+pub fn get_elements_from_config(
+    config_elements: &[ConfigElement],
+    prompt: &Arc<Mutex<Prompt>>,
+) -> Result<Vec<ElementType>, EASHError> {
     let mut elements: Vec<ElementType> = Vec::new();
-    for i in config.chain_elements.iter() {
+    for i in config_elements.iter() {
         match i {
             ConfigElement::BasicElement {
                 content,
                 visual_state,
+                on_click,
             } => {
                 // MOST MEMORY DUPLICATING CODE AWARD: me 🎖️
                 let vs: VisualState = visual_state.clone().try_into()?;
                 elements.push(ElementType::BasicElement(BasicElement {
                     content: content.clone(),
                     visual_state: vs,
+                    on_click: click_action(on_click)?,
                 }));
             }
+            ConfigElement::ModeIndicator {
+                insert,
+                normal,
+                visual,
+                on_click,
+            } => {
+                elements.push(ElementType::ModeIndicator(Box::new(ModeIndicator {
+                    insert: insert.clone().try_into()?,
+                    normal: normal.clone().try_into()?,
+                    visual: visual.clone().try_into()?,
+                    prompt: prompt.clone(),
+                    on_click: click_action(on_click)?,
+                })));
+            }
+            ConfigElement::Command {
+                empty,
+                visual_state,
+                on_click,
+            } => {
+                let element = BasicElement {
+                    content: empty.clone(),
+                    visual_state: visual_state.clone().try_into()?,
+                    on_click: click_action(on_click)?,
+                };
+                let empty = empty.clone();
+                let trigger = Box::new(move |update: &PromptUpdate| {
+                    match current_command(update.tokens) {
+                        // (an @ would be taken for the start of a glyph)
+                        Some(command) => command.replace('@', "＠"),
+                        None => empty.clone(),
+                    }
+                });
+                let reactive = ReactiveElement::new(element, prompt.clone(), trigger);
+                elements.push(ElementType::Reactive(Box::new(reactive)));
+            }
+            ConfigElement::Completions {
+                visual_state,
+                on_click,
+            } => {
+                let element = BasicElement {
+                    content: String::new(),
+                    visual_state: visual_state.clone().try_into()?,
+                    on_click: click_action(on_click)?,
+                };
+                let trigger = Box::new(|update: &PromptUpdate| {
+                    update.completions.unwrap_or_default().replace('@', "＠")
+                });
+                let reactive = ReactiveElement::new(element, prompt.clone(), trigger);
+                elements.push(ElementType::Reactive(Box::new(reactive)));
+            }
             ConfigElement::Prompt => {
                 return Err(EASHError::ConfigPromptUsed);
             }
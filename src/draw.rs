@@ -1,206 +1,565 @@
 use crate::{
-    chain::{Chain, ChainLink},
+    chain::{Chain, ChainLink, ChainMass, LEFT_MARGIN, RIGHT_MARGIN},
     config::GlyphList,
     element::{BasicElement, ElementType},
     error::EASHError,
-    evaluate::{TokenType, tokenize},
-    misc_types::{Alignment, Glyph, Width},
+    evaluate::TokenType,
+    misc_types::{Alignment, Color, Direction, EASHPallete, Glyph, Overflow, VisualState, Width},
+    pairs::highlight_brackets,
+    prompt::display_width,
+    scheduler::earliest,
+    surface::{Style, Surface},
+    vi::ViConfig,
 };
 
-use crossterm::{
-    cursor::MoveToColumn,
-    queue,
-    style::{
-        Color as ctColor, Print, PrintStyledContent, ResetColor, SetBackgroundColor,
-        SetForegroundColor, Stylize,
-    },
-    terminal::{Clear, ClearType},
+use crossterm::style::Color as ctColor;
+use std::{
+    cmp::Reverse,
+    sync::MutexGuard,
+    time::{Duration, Instant},
 };
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
-use std::{io::Write, sync::MutexGuard};
+const ABBREVIATION_FLASH: Duration = Duration::from_millis(400);
+// the elements give up room before the input gets any narrower than this
+const MIN_INPUT_WIDTH: usize = 20;
 
-// returns string with padding, content start & content end
+// returns string with padding, content start & content end (in terminal columns, not bytes)
 pub fn pad_string(original: String, size: u16, aligment: &Alignment) -> (String, usize, usize) {
-    let mut s = original;
-    if s.len() >= size as usize {
-        let len = s.len();
-        return (s, 0, len);
+    let width = display_width(&original);
+    let size = size as usize;
+    if width >= size {
+        return (original, 0, width);
     }
 
-    let difference = size as usize - s.len();
-    let start;
-    let end;
+    let difference = size - width;
     match aligment {
-        Alignment::Left => {
-            start = 0;
-            end = s.len();
-            s = format!("{}{}", s, " ".repeat(difference));
-        }
+        Alignment::Left => (format!("{}{}", original, " ".repeat(difference)), 0, width),
         Alignment::Center => {
             let l = difference / 2;
             let r = difference - l;
-            s = format!("{}{}{}", " ".repeat(l), s, "".repeat(r));
-            start = l;
-            end = s.len() - r;
+            let s = format!("{}{}{}", " ".repeat(l), original, " ".repeat(r));
+            (s, l, l + width)
         }
-        Alignment::Right => {
-            s = format!("{}{}", " ".repeat(difference), s);
-            start = difference;
-            end = s.len();
+        Alignment::Right => (
+            format!("{}{}", " ".repeat(difference), original),
+            difference,
+            size,
+        ),
+    }
+}
+
+// the first `columns` columns worth of s. a wide character that'd get cut in half is left out
+pub fn truncate_to_width(s: &str, columns: usize) -> String {
+    let mut width = 0;
+    let mut out = String::new();
+    for grapheme in s.graphemes(true) {
+        width += grapheme.width();
+        if width > columns {
+            break;
         }
-    };
+        out.push_str(grapheme);
+    }
+    out
+}
 
-    (s, start, end)
+// the graphemes of s that are still visible with the first `hidden` columns cut off, with the column
+// each one starts at. a wide character that's only half hidden turns into spaces
+pub fn visible_graphemes(s: &str, hidden: usize) -> Vec<(usize, &str)> {
+    let mut visible = Vec::new();
+    let mut column = 0;
+    for grapheme in s.graphemes(true) {
+        let width = grapheme.width();
+        if column >= hidden {
+            visible.push((column, grapheme));
+        } else if column + width > hidden {
+            for c in hidden..column + width {
+                visible.push((c, " "));
+            }
+        }
+        column += width;
+    }
+    visible
+}
+
+// how much of an element is behind the first terminal column & gets cut off
+fn hidden_columns(mass: &ChainMass) -> usize {
+    if mass.position.round() < 0.0 {
+        mass.position.round().abs() as usize
+    } else {
+        0
+    }
 }
 
-pub fn draw_flat_basic_element<W: Write>(
-    w: &mut W,
-    item: &ChainLink,
+pub fn draw_flat_basic_element<S: Surface>(
+    s: &mut S,
+    mass: &ChainMass,
     e: &BasicElement,
     content: String,
 ) -> Result<(), EASHError> {
-    let mut print = &content[0..];
-    // cut the string off if its behind the first terminal character
-    if item.mass.position.round() < 0.0 {
-        let difference = item.mass.position.round().abs() as usize;
-        // don't print the string
-        if difference >= print.len() {
-            return Ok(());
+    s.set_style(Style::new(
+        e.visual_state.color.to_flat_color()?,
+        e.visual_state.bg_color.to_flat_color()?,
+    ))?;
+    for (_, grapheme) in visible_graphemes(&content, hidden_columns(mass)) {
+        s.put(grapheme)?;
+    }
+    Ok(())
+}
+
+// what's on screen around a link, so its caps can be drawn on top of it
+#[derive(Clone, Copy)]
+pub struct Neighbours {
+    // the backgrounds under the left & right caps
+    pub left: ctColor,
+    pub right: ctColor,
+    // the link before runs right into this one & already has a cap pointing into it
+    pub left_merged: bool,
+}
+
+impl Default for Neighbours {
+    fn default() -> Self {
+        Neighbours {
+            left: ctColor::Reset,
+            right: ctColor::Reset,
+            left_merged: false,
+        }
+    }
+}
+
+// where a link was last frame & what colors its edges are
+#[derive(Clone, Copy)]
+struct Edge {
+    start: i32,
+    end: i32,
+    left_bg: ctColor,
+    right_bg: ctColor,
+    right_cap: bool,
+}
+
+// the element a link is showing right now, if it's got one (and whatever it needs isn't locked)
+fn element_of(link: &ChainLink) -> Option<&BasicElement> {
+    match &link.element {
+        ElementType::BasicElement(e) => Some(e),
+        ElementType::ModeIndicator(m) => {
+            let mode = m.prompt.try_lock().ok()?.vi_mode;
+            Some(m.element_for(mode))
         }
-        // TODO)) looks horrifically inefficient, pls efficientize this
-        let chardif = print
-            .char_indices()
-            .enumerate()
-            .find_map(|(i, (ci, _))| {
-                if i == difference {
-                    return Some(ci);
+        ElementType::Reactive(r) => Some(&r.element),
+        // (the prompt doesn't have a background)
+        ElementType::Prompt(_) => None,
+    }
+}
+
+fn edge_of(link: &ChainLink) -> Option<Edge> {
+    if link.mass.hidden {
+        return None;
+    }
+    let vs = &element_of(link)?.visual_state;
+    let start = link.mass.position.round() as i32;
+    Some(Edge {
+        start,
+        end: start + link.mass.width as i32,
+        left_bg: vs.bg_color.to_color_for_char(0.0),
+        right_bg: vs.bg_color.to_color_for_char(1.0),
+        right_cap: !vs.right_cap.glyph(Direction::Right).is_empty(),
+    })
+}
+
+// the links move around on their own, so the caps have to deal with whatever they end up next to:
+// a gap shows the terminal's background, a link right up against it (or under it) shows that one's
+pub fn neighbours_of(links: &[ChainLink], i: usize) -> Neighbours {
+    let Some(me) = edge_of(&links[i]) else {
+        return Neighbours::default();
+    };
+    let previous = i.checked_sub(1).and_then(|p| edge_of(&links[p]));
+    let next = links.get(i + 1).and_then(edge_of);
+
+    let mut neighbours = Neighbours::default();
+    if let Some(p) = previous {
+        if p.start <= me.start && me.start < p.end {
+            neighbours.left = p.right_bg;
+        }
+        neighbours.left_merged = p.right_cap && p.end == me.start;
+    }
+    if let Some(n) = next
+        && n.start <= me.end
+        && me.end <= n.end
+    {
+        neighbours.right = n.left_bg;
+    }
+    neighbours
+}
+
+// the element's content with its glyphs filled in, and when that's going to look different
+fn glyphed_content(
+    e: &BasicElement,
+    glyphs: &GlyphList,
+) -> Result<(String, Option<Instant>), EASHError> {
+    let mut next_change = None;
+    // glyph logic!
+    let glyphed = if e.content.contains("@") {
+        let mut processed = String::new();
+        for (i, gl) in e.content.split('@').enumerate() {
+            if i == 0 && !e.content.starts_with("@") {
+                processed.push_str(gl);
+                continue;
+            }
+
+            // wacky hack: if its empty, was probably a @@ so add an actual @ (:
+            if gl.is_empty() {
+                processed.push('@');
+                continue;
+            } else if gl.starts_with(' ') {
+                processed.push(' ');
+                continue;
+            }
+
+            // greedy search: get longest possible string that matches this
+            // in the future we shouldnt even need to process this during rendering
+            let mut longest_match: Option<(&Glyph, usize)> = None;
+            for (cpos, _) in gl.char_indices() {
+                if let Some(g) = glyphs.list.get(&gl[0..cpos + 1]) {
+                    longest_match = Some((g, cpos + 1));
                 }
-                None
-            })
-            .unwrap();
-        (_, print) = content.split_at(chardif);
+            }
+
+            if let Some((glyph, split)) = longest_match {
+                let (_, after) = gl.split_at(split);
+                processed.push(glyph.get_current_glyph(&glyphs.instant));
+                next_change = earliest(next_change, glyph.next_change(&glyphs.instant));
+                processed.push_str(after);
+            } else {
+                return Err(EASHError::ConfigInvalidGlyph(gl.to_string()));
+            }
+        }
+        Some(processed)
+    } else {
+        None
+    };
+
+    Ok((glyphed.unwrap_or_else(|| e.content.clone()), next_change))
+}
+
+// the caps on both ends
+fn caps_width(vs: &VisualState) -> usize {
+    display_width(vs.left_cap.glyph(Direction::Left))
+        + display_width(vs.right_cap.glyph(Direction::Right))
+}
+
+// content cut down to fit in columns (padding included), with a … on the end
+fn ellipsize(content: &str, padding: usize, columns: usize) -> String {
+    let pad = " ".repeat(padding);
+    match columns.checked_sub(2 * padding + 1) {
+        Some(room) => format!("{pad}{}…{pad}", truncate_to_width(content, room)),
+        None => truncate_to_width(&format!("{pad}…"), columns),
     }
+}
 
-    let styled = print
-        .stylize()
-        .with(e.visual_state.color.to_flat_color()?)
-        .on(e.visual_state.bg_color.to_flat_color()?);
-    queue!(w, PrintStyledContent(styled))?;
-    Ok(())
+// the element's text padded & cut to its width, with where the content starts & ends. room is the
+// most it's allowed to take up (not counting the caps) when there isn't enough to go around
+pub fn layout(vs: &VisualState, content: &str, room: Option<usize>) -> (String, usize, usize) {
+    let padding = vs.padding as usize;
+    // add spacing
+    let print = format!("{}{}{}", " ".repeat(padding), content, " ".repeat(padding));
+
+    // pad string if too small, cut it if its too big.
+    let (cut_at, size) = match vs.width {
+        Width::Minimum(m) => (None, m as usize),
+        Width::Units(u) => (Some(u as usize), u as usize),
+    };
+    let (cut_at, size) = match room {
+        Some(room) => (Some(cut_at.map_or(room, |c| c.min(room))), size.min(room)),
+        None => (cut_at, size),
+    };
+    let print = match cut_at {
+        Some(c) if display_width(&print) > c && vs.overflow == Overflow::Ellipsis => {
+            ellipsize(content, padding, c)
+        }
+        Some(c) => truncate_to_width(&print, c),
+        None => print,
+    };
+    let (print, mut start, mut end) = pad_string(print, size as u16, &vs.align);
+
+    // (if it got cut there might not be any padding left to take off the end)
+    start += padding;
+    end = end.saturating_sub(padding).max(start);
+    (print, start, end)
+}
+
+// how wide an element would be if it had all the room it wanted
+pub fn natural_width(e: &BasicElement, glyphs: &GlyphList) -> Result<usize, EASHError> {
+    let (content, _) = glyphed_content(e, glyphs)?;
+    Ok(caps_width(&e.visual_state) + display_width(&layout(&e.visual_state, &content, None).0))
+}
+
+// how much room an element gets when there isn't enough for everybody
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Fit {
+    Whole,
+    // at most this many columns, caps included
+    Cut(usize),
+    Hidden,
+}
+
+// works out what each element has to give up so they all fit in budget columns. the lowest priority
+// ones give up room first (the one further right if it's a tie), anything without a visual state
+// (the prompt, or something that was locked) just takes up what it takes up
+pub fn fit(wanted: &[(usize, Option<&VisualState>)], spacing: usize, budget: usize) -> Vec<Fit> {
+    let mut fits = vec![Fit::Whole; wanted.len()];
+    let total: usize = wanted
+        .iter()
+        .filter(|(width, _)| *width > 0)
+        .map(|(width, _)| width + spacing)
+        .sum();
+    let mut over = total.saturating_sub(budget);
+
+    let mut order: Vec<(usize, &VisualState)> = wanted
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (_, vs))| vs.map(|vs| (i, vs)))
+        .collect();
+    order.sort_by_key(|&(i, vs)| (vs.priority, Reverse(i)));
+    for (i, vs) in order {
+        if over == 0 {
+            break;
+        }
+        let width = wanted[i].0;
+        match vs.overflow {
+            Overflow::Clip => {}
+            Overflow::Hide => {
+                fits[i] = Fit::Hidden;
+                over = over.saturating_sub(width + spacing);
+            }
+            Overflow::Ellipsis | Overflow::Shrink => {
+                // always leave at least a column of content
+                let minimum = caps_width(vs) + 2 * vs.padding as usize + 1;
+                let give = over.min(width.saturating_sub(minimum));
+                if give > 0 {
+                    fits[i] = Fit::Cut(width - give);
+                    over -= give;
+                }
+            }
+        }
+    }
+    fits
+}
+
+// what each link gets to take up this frame, going by what they all want right now
+fn fit_links(
+    links: &[ChainLink],
+    spacing: u16,
+    budget: usize,
+    glyphs: &GlyphList,
+) -> Result<Vec<Fit>, EASHError> {
+    let mut wanted = Vec::new();
+    for link in links {
+        wanted.push(match (&link.element, element_of(link)) {
+            (ElementType::Prompt(_), _) => (0, None),
+            (_, Some(e)) => (natural_width(e, glyphs)?, Some(&e.visual_state)),
+            // (something's locked, so go with what it took up last frame)
+            (_, None) => (link.mass.width as usize, None),
+        });
+    }
+    Ok(fit(&wanted, spacing as usize, budget))
+}
+
+// returns when the element will look different next (if it's animated)
+pub fn draw_basic_element<S: Surface>(
+    s: &mut S,
+    mass: &mut ChainMass,
+    e: &BasicElement,
+    glyphs: &GlyphList,
+    neighbours: Neighbours,
+    fit: Fit,
+) -> Result<Option<Instant>, EASHError> {
+    let (content, next_change) = glyphed_content(e, glyphs)?;
+    mass.hidden = fit == Fit::Hidden;
+    if mass.hidden {
+        mass.width = 0;
+        return Ok(next_change);
+    }
+
+    let room = match fit {
+        Fit::Cut(columns) => Some(columns.saturating_sub(caps_width(&e.visual_state))),
+        _ => None,
+    };
+    let (print, start, end) = layout(&e.visual_state, &content, room);
+
+    let vs = &e.visual_state;
+    let left_cap = vs.left_cap.glyph(Direction::Left);
+    let right_cap = vs.right_cap.glyph(Direction::Right);
+    let body_width = display_width(&print);
+    mass.width = (display_width(left_cap) + body_width + display_width(right_cap)) as u16;
+
+    // style & print element as required (character at a time if its a gradient or has caps)
+    if vs.bg_color.is_gradient() || vs.color.is_gradient() || mass.width as usize > body_width {
+        let color_at = |color: &Color, i: usize| match color {
+            Color::Gradient(g) => {
+                color.to_color_for_char(g.position_of(i, body_width, (start, end)))
+            }
+            _ => color.to_color_for_char(0.0),
+        };
+        let body_bg = |i| color_at(&vs.bg_color, i);
+
+        // running straight into the link before means its cap already points into us, so ours
+        // just becomes more background
+        let left_cap = match neighbours.left_merged {
+            true => " ".repeat(display_width(left_cap)),
+            false => left_cap.to_string(),
+        };
+        let offset = display_width(&left_cap);
+        let line = format!("{left_cap}{print}{right_cap}");
+
+        let mut current_style = None;
+        for (column, grapheme) in visible_graphemes(&line, hidden_columns(mass)) {
+            let style = if column < offset && neighbours.left_merged {
+                Style::new(ctColor::Reset, body_bg(0))
+            } else if column < offset {
+                Style::new(body_bg(0), neighbours.left)
+            } else if column >= offset + body_width {
+                Style::new(body_bg(body_width.saturating_sub(1)), neighbours.right)
+            } else {
+                Style::new(
+                    color_at(&vs.color, column - offset),
+                    body_bg(column - offset),
+                )
+            };
+            if current_style != Some(style) {
+                s.set_style(style)?;
+                current_style = Some(style);
+            }
+            s.put(grapheme)?;
+        }
+    } else {
+        draw_flat_basic_element(s, mass, e, print)?;
+    }
+
+    Ok(next_change)
+}
+
+// anything but the prompt, wherever the surface's cursor is. returns when it'll look different next
+fn draw_element<S: Surface>(
+    s: &mut S,
+    item: &mut ChainLink,
+    glyphs: &GlyphList,
+    neighbours: Neighbours,
+    fit: Fit,
+) -> Result<Option<Instant>, EASHError> {
+    match &item.element {
+        ElementType::BasicElement(e) => {
+            draw_basic_element(s, &mut item.mass, e, glyphs, neighbours, fit)
+        }
+        ElementType::ModeIndicator(m) => {
+            // something was busy, so this frame's missing a bit & we need another one soon
+            let Ok(lock) = m.prompt.try_lock() else {
+                return Ok(Some(Instant::now()));
+            };
+            let mode = lock.vi_mode;
+            drop(lock);
+            let element = m.element_for(mode);
+            draw_basic_element(s, &mut item.mass, element, glyphs, neighbours, fit)
+        }
+        ElementType::Reactive(r) => {
+            draw_basic_element(s, &mut item.mass, &r.element, glyphs, neighbours, fit)
+        }
+        ElementType::Prompt(_) => Ok(None),
+    }
+}
+
+// reactive elements catch up with the prompt. false if one of them couldn't get at it
+fn run_triggers(links: &mut [ChainLink]) -> bool {
+    let mut caught_up = true;
+    for link in links {
+        if let ElementType::Reactive(r) = &mut link.element {
+            caught_up &= r.update();
+        }
+    }
+    caught_up
+}
+
+// a chain without the prompt in it, all on one row
+fn draw_side_chain<S: Surface>(
+    s: &mut S,
+    links: &mut [ChainLink],
+    row: u16,
+    glyphs: &GlyphList,
+    fits: &[Fit],
+) -> Result<Option<Instant>, EASHError> {
+    let neighbours: Vec<Neighbours> = (0..links.len()).map(|i| neighbours_of(links, i)).collect();
+    let mut next_change = None;
+    for ((item, &neighbours), &fit) in links.iter_mut().zip(&neighbours).zip(fits) {
+        s.move_to(item.mass.position.round() as u16, row)?;
+        let next = draw_element(s, item, glyphs, neighbours, fit)?;
+        next_change = earliest(next_change, next);
+    }
+    s.set_style(Style::default())?;
+    Ok(next_change)
 }
 
 // we need it to be mutable to set the width property on mass
+// returns when the next frame would look different even if nothing happens (animations and such)
 // TODO)) split this function up
-pub fn draw<W: Write + Send>(
-    w: &mut W,
+pub fn draw<S: Surface>(
+    s: &mut S,
     elements: &mut MutexGuard<Chain>,
     glyphs: &GlyphList,
-) -> Result<(), EASHError> {
-    _ = queue!(w, MoveToColumn(0), Clear(ClearType::CurrentLine));
+    pallete: &EASHPallete,
+    vi: &ViConfig,
+) -> Result<Option<Instant>, EASHError> {
+    // get rid of everything from last frame
+    s.clear()?;
 
     let mut cursor_position = 0;
-    for item in elements.links.iter_mut() {
-        let position = item.mass.position.round() as u16;
-        queue!(w, MoveToColumn(position))?;
-
-        // draw each element based on its enum 😨😨😨
-        match &item.element {
-            ElementType::BasicElement(e) => {
-                // glyph logic!
-                let glyphed = if e.content.contains("@") {
-                    let mut processed = String::new();
-                    for (i, gl) in e.content.split('@').enumerate() {
-                        if i == 0 && !e.content.starts_with("@") {
-                            processed.push_str(gl);
-                            continue;
-                        }
+    let mut cursor_row = 0;
+    let mut rows = 1;
+    let mut cursor_style = None;
+    let mut next_change = None;
+    // something was busy, so this frame's missing a bit & we need another one soon
+    let locked_out = Some(Instant::now());
+    let top = elements.input_row();
+    let columns = elements.columns as usize;
 
-                        // wacky hack: if its empty, was probably a @@ so add an actual @ (:
-                        if gl.is_empty() {
-                            processed.push('@');
-                            continue;
-                        } else if gl.starts_with(' ') {
-                            processed.push(' ');
-                            continue;
-                        }
-
-                        // greedy search: get longest possible string that matches this
-                        // in the future we shouldnt even need to process this during rendering
-                        let mut longest_match: Option<(&Glyph, usize)> = None;
-                        for (cpos, _) in gl.char_indices() {
-                            if let Some(g) = glyphs.list.get(&gl[0..cpos + 1]) {
-                                longest_match = Some((g, cpos + 1));
-                            }
-                        }
-
-                        if let Some((glyph, split)) = longest_match {
-                            let (_, after) = gl.split_at(split);
-                            processed.push(glyph.get_current_glyph(&glyphs.instant));
-                            processed.push_str(after);
-                        } else {
-                            return Err(EASHError::ConfigInvalidGlyph(gl.to_string()));
-                        }
-                    }
-                    Some(processed)
-                } else {
-                    None
-                };
-
-                let base = match &glyphed {
-                    Some(g) => g,
-                    None => &e.content,
-                };
-
-                // add spacing
-                let mut print = format!(
-                    "{}{}{}",
-                    " ".repeat(e.visual_state.padding as usize),
-                    base,
-                    " ".repeat(e.visual_state.padding as usize)
-                );
+    // (before anything gets measured, what they say decides how wide they are)
+    let mut caught_up = run_triggers(&mut elements.links);
+    if let Some(right) = &mut elements.right {
+        caught_up &= run_triggers(&mut right.links);
+    }
+    if let Some(above) = &mut elements.above {
+        caught_up &= run_triggers(&mut above.links);
+    }
+    if !caught_up {
+        next_change = locked_out;
+    }
 
-                // pad string if too small, cut it if its too big.
-                let (mut start, mut end);
-                match e.visual_state.width {
-                    Width::Minimum(m) => {
-                        (print, start, end) = pad_string(print, m as u16, &e.visual_state.align);
-                    }
-                    Width::Units(u) => {
-                        (print, start, end) = pad_string(print, u as u16, &e.visual_state.align);
-                    }
-                }
+    if let Some(above) = &mut elements.above {
+        let budget = columns.saturating_sub(LEFT_MARGIN as usize);
+        let fits = fit_links(&above.links, above.spring.spacing, budget, glyphs)?;
+        let next = draw_side_chain(s, &mut above.links, 0, glyphs, &fits)?;
+        next_change = earliest(next_change, next);
+    }
 
-                start += e.visual_state.padding as usize;
-                end -= e.visual_state.padding as usize;
-
-                item.mass.width = print.len() as u16;
-
-                // style & print element as required (character at a time if its a gradient)
-                if e.visual_state.bg_color.is_gradient() || e.visual_state.color.is_gradient() {
-                    let fg = e.visual_state.color.to_color_for_char(0.0);
-                    let bg = e.visual_state.bg_color.to_color_for_char(0.0);
-                    queue!(w, SetBackgroundColor(bg), SetForegroundColor(fg))?;
-
-                    for (i, character) in print.chars().enumerate() {
-                        let mut character = character.to_string().stylize();
-                        if i >= start && i <= end && e.visual_state.color.is_gradient() {
-                            let color = e
-                                .visual_state
-                                .color
-                                .to_color_for_char((i - start / end) as f32);
-                            character = character.with(color)
-                        }
+    // the elements squeeze up (going by their overflow) to leave the input some room
+    let budget = columns.saturating_sub(LEFT_MARGIN as usize + MIN_INPUT_WIDTH);
+    let fits = fit_links(&elements.links, elements.spring.spacing, budget, glyphs)?;
+    let mut scroll = elements.scroll as usize;
 
-                        if e.visual_state.bg_color.is_gradient() {
-                            character = character
-                                .on(e.visual_state.color.to_color_for_char((i / end) as f32))
-                        }
+    // (from where everything was last frame, the widths only get worked out while drawing)
+    let neighbours: Vec<Neighbours> = (0..elements.links.len())
+        .map(|i| neighbours_of(&elements.links, i))
+        .collect();
+    for ((item, &neighbours), &fit) in elements.links.iter_mut().zip(&neighbours).zip(&fits) {
+        let position = item.mass.position.round() as u16;
+        s.move_to(position, top)?;
 
-                        queue!(w, PrintStyledContent(character))?;
-                    }
-                } else {
-                    draw_flat_basic_element(w, item, e, print)?;
-                }
+        // draw each element based on its enum 😨😨😨
+        match &item.element {
+            ElementType::BasicElement(_)
+            | ElementType::ModeIndicator(_)
+            | ElementType::Reactive(_) => {
+                let next = draw_element(s, item, glyphs, neighbours, fit)?;
+                next_change = earliest(next_change, next);
             }
             ElementType::Prompt(pm) => {
                 let lock_result = pm.try_lock(); // idk how to convert a mutex error to an eash error
@@ -209,54 +568,468 @@ pub fn draw<W: Write + Send>(
                 if let Ok(l) = lock_result {
                     lock = l;
                 } else {
+                    next_change = locked_out;
                     continue;
                 }
-                cursor_position = item.mass.position.round() as u16 + lock.cursor_position;
-                queue!(w, ResetColor)?;
+                // continuation lines get lined up under the start of the prompt
+                let indent = item.mass.position.round() as u16;
 
-                let tokens = tokenize(&lock.prompt);
-                if tokens.is_empty() {
-                    continue;
+                // whatever doesn't fit in the rest of the line scrolls sideways, following the cursor
+                let room = columns.saturating_sub(indent as usize).max(1);
+                let column = lock.cursor_column();
+                let widest = lock
+                    .text()
+                    .split('\n')
+                    .map(display_width)
+                    .max()
+                    .unwrap_or(0);
+                scroll = scroll.min((widest + 1).saturating_sub(room));
+                if column < scroll {
+                    scroll = column;
+                } else if column >= scroll + room {
+                    scroll = column + 1 - room;
                 }
+                cursor_position = indent + (column - scroll) as u16;
+                cursor_row = top + lock.cursor_line() as u16;
+                rows = top + lock.line_count() as u16;
+                cursor_style = lock.vi_mode.map(|m| vi.cursor_for(m));
+
+                let brackets = highlight_brackets(lock.text(), lock.tokens(), lock.cursor_position);
 
                 // Oh my Performance Bruh
                 let mut colors = Vec::new();
-                for token in tokens {
-                    // temporary logic....
+                for token in lock.tokens() {
                     let color = match token.contents {
-                        TokenType::Value(_) => ctColor::White,
-                        TokenType::Flag(_) => ctColor::Red,
-                        TokenType::Directory(_) => ctColor::Yellow,
-                        TokenType::String(_) => ctColor::Green,
-                        TokenType::AndThen => ctColor::Magenta,
-                        TokenType::Pipe => ctColor::Cyan,
+                        TokenType::Value(_) => &pallete.value_fg,
+                        TokenType::Flag(_) => &pallete.flag_fg,
+                        TokenType::Directory(_) => &pallete.directory_fg,
+                        TokenType::String(_) => &pallete.string_fg,
+                        TokenType::AndThen => &pallete.andthen_fg,
+                        TokenType::Pipe => &pallete.pipe_fg,
                     };
-                    colors.push((token.start, color));
+                    colors.push((token.start, color.to_color_for_char(0.0)));
+                }
+
+                let selection = Style::new(
+                    pallete.selection_fg.to_color_for_char(0.0),
+                    pallete.selection_bg.to_color_for_char(0.0),
+                );
+                let bracket_match_bg = pallete.bracket_match_bg.to_color_for_char(0.0);
+                let bracket_unbalanced_fg = pallete.bracket_unbalanced_fg.to_color_for_char(0.0);
+                let abbreviation_bg = pallete.abbreviation_bg.to_color_for_char(0.0);
+                // a freshly expanded abbreviation lights up for a moment
+                let flashing = lock
+                    .expanded
+                    .filter(|(.., when)| when.elapsed() < ABBREVIATION_FLASH);
+                if let Some((.., when)) = flashing {
+                    next_change = earliest(next_change, Some(when + ABBREVIATION_FLASH));
                 }
+                let flashing = flashing.map(|(start, end, _)| start..end);
 
                 let mut color_index = 0;
-                let (_, first_color) = colors.first().unwrap();
-                queue!(w, SetForegroundColor(*first_color))?;
-                for (position, character) in lock.prompt.chars().enumerate() {
-                    let color = colors.get(color_index + 1);
-                    if let Some((ni, nc)) = color {
-                        if *ni == position { 
-                            queue!(w, SetForegroundColor(*nc))?;
-                            color_index += 1;
+                // (an empty or all whitespace prompt has no tokens, but the newlines still need drawing)
+                let mut token_color = colors.first().map_or(ctColor::Reset, |&(_, c)| c);
+                let mut current_style = None;
+                let mut row = top;
+                let mut line_column = 0;
+                for (position, grapheme) in lock.text().grapheme_indices(true) {
+                    while let Some((ni, nc)) = colors.get(color_index + 1)
+                        && *ni <= position
+                    {
+                        token_color = *nc;
+                        color_index += 1;
+                    }
+
+                    if grapheme == "\n" {
+                        row += 1;
+                        line_column = 0;
+                        s.move_to(indent, row)?;
+                        continue;
+                    }
+
+                    let (start, end) = (line_column, line_column + display_width(grapheme));
+                    line_column = end;
+                    // off either side of what's showing (half a wide character just leaves a gap)
+                    if end > scroll + room || (start < scroll && end <= scroll) {
+                        continue;
+                    }
+                    let gap;
+                    let grapheme = match start < scroll {
+                        true => {
+                            gap = " ".repeat(end - scroll);
+                            gap.as_str()
                         }
+                        false => grapheme,
+                    };
+
+                    let mut style = match lock.position_is_in_selection(position) {
+                        true => selection,
+                        false => Style::new(token_color, ctColor::Reset),
+                    };
+                    // characters that get their own colors
+                    if brackets.is_unbalanced(position) {
+                        style.fg = bracket_unbalanced_fg;
+                    } else if brackets.is_matching(position) {
+                        style.bg = bracket_match_bg;
+                    } else if flashing.as_ref().is_some_and(|r| r.contains(&position)) {
+                        style.bg = abbreviation_bg;
                     }
 
-                    queue!(w, Print(character))?;
+                    // only bother the surface when the colors actually change
+                    if current_style != Some(style) {
+                        s.set_style(style)?;
+                        current_style = Some(style);
+                    }
+                    s.put(grapheme)?;
                 }
+                s.set_style(Style::default())?;
 
-                item.mass.width = lock.prompt.len() as u16;
+                item.mass.width = lock.width().saturating_sub(scroll).min(room) as u16;
             }
         }
-        w.flush()?;
     }
-    // if theres no cursor position then set it
-    queue!(w, MoveToColumn(cursor_position))?;
-    w.flush()?;
+    // (after the input, so it's on top if they end up overlapping anyway)
+    // it squeezes up to leave the input the same room the main chain does, past that links hide instead
+    let input_start = elements
+        .links
+        .last()
+        .map_or(0, |l| l.mass.position.round() as usize);
+    if let Some(right) = &mut elements.right {
+        let budget = columns.saturating_sub(input_start + MIN_INPUT_WIDTH + RIGHT_MARGIN as usize);
+        let spacing = right.spring.spacing;
+        let links = right.visible_mut();
+        let fits = fit_links(links, spacing, budget, glyphs)?;
+        let next = draw_side_chain(s, links, top, glyphs, &fits)?;
+        next_change = earliest(next_change, next);
+    }
 
-    Ok(())
+    s.move_to(cursor_position, cursor_row)?;
+    elements.scroll = scroll as u16;
+    elements.rows = rows;
+    elements.cursor_row = cursor_row;
+    if let Some(style) = cursor_style {
+        s.set_cursor_style(style)?;
+    }
+    s.flush()?;
+
+    Ok(next_change)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chain::{Chain, ChainLink, ChainMass, SideChain},
+        config::GlyphList,
+        draw::{Fit, draw, fit, layout, pad_string, truncate_to_width, visible_graphemes},
+        element::{BasicElement, ElementType, PromptUpdate, ReactiveElement},
+        evaluate::current_command,
+        gradient::{Coverage, Gradient, Interpolation},
+        misc_types::{
+            Alignment, Cap, Color, EASHPallete, HexColor, Overflow, Spring, VisualState, Width,
+        },
+        prompt::Prompt,
+        surface::{GridSurface, Style},
+        undo::EditKind,
+        vi::ViConfig,
+    };
+    use crossterm::style::Color as ctColor;
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    fn link(position: f32, element: ElementType) -> ChainLink {
+        ChainLink {
+            mass: ChainMass {
+                mass: 1.0,
+                position,
+                velocity: 0.0,
+                width: 0,
+                hidden: false,
+            },
+            element,
+        }
+    }
+
+    fn element(content: &str, visual_state: VisualState) -> ElementType {
+        ElementType::BasicElement(BasicElement {
+            visual_state,
+            content: content.to_string(),
+            on_click: None,
+        })
+    }
+
+    // a chain of just these links, the tests that want a prompt put one in themselves
+    fn chain_with(columns: u16, links: Vec<ChainLink>) -> Mutex<Chain> {
+        Mutex::new(Chain {
+            spring: Spring {
+                spacing: 1,
+                constant: 1.0,
+                dampening: 1.0,
+            },
+            links,
+            rows: 1,
+            cursor_row: 0,
+            top_row: 0,
+            repaint: false,
+            right: None,
+            above: None,
+            columns,
+            scroll: 0,
+        })
+    }
+
+    fn frame(lock: &mut MutexGuard<Chain>) -> GridSurface {
+        let mut grid = GridSurface::new(lock.columns);
+        draw(
+            &mut grid,
+            lock,
+            &GlyphList::default(),
+            &EASHPallete::default(),
+            &ViConfig::default(),
+        )
+        .unwrap();
+        grid
+    }
+
+    #[test]
+    fn draws_onto_a_grid() {
+        let mut prompt = Prompt::default();
+        prompt.insert_str("echo hi\n| cat", EditKind::Typing);
+        let chain = chain_with(
+            20,
+            vec![
+                link(0.0, element("eash", VisualState::default())),
+                link(7.0, ElementType::Prompt(Arc::new(Mutex::new(prompt)))),
+            ],
+        );
+
+        let mut lock = chain.lock().unwrap();
+        let grid = frame(&mut lock);
+        assert_eq!(grid.text(), " eash  echo hi\n       | cat");
+        assert_eq!(grid.cursor, (12, 1));
+        assert_eq!((lock.rows, lock.cursor_row), (2, 1));
+        assert_eq!(lock.links[0].mass.width, 6);
+        assert_eq!(
+            grid.cell(1, 0).unwrap().style.fg,
+            ctColor::Rgb {
+                r: 255,
+                g: 255,
+                b: 255
+            }
+        );
+        assert_eq!(
+            grid.cell(7, 1).unwrap().style.fg,
+            EASHPallete::default().pipe_fg.to_color_for_char(0.0)
+        );
+    }
+
+    #[test]
+    fn reactive_elements_follow_the_prompt() {
+        let prompt = Arc::new(Mutex::new(Prompt::default()));
+        prompt
+            .lock()
+            .unwrap()
+            .insert_str("ls | grep x", EditKind::Typing);
+        let element = BasicElement {
+            visual_state: VisualState::default(),
+            content: String::new(),
+            on_click: None,
+        };
+        let trigger = Box::new(|update: &PromptUpdate| {
+            current_command(update.tokens).unwrap_or("-").to_string()
+        });
+        let reactive = ReactiveElement::new(element, prompt.clone(), trigger);
+        let chain = chain_with(
+            30,
+            vec![
+                link(0.0, ElementType::Reactive(Box::new(reactive))),
+                link(8.0, ElementType::Prompt(prompt.clone())),
+            ],
+        );
+
+        let mut lock = chain.lock().unwrap();
+        assert_eq!(frame(&mut lock).text(), " grep   ls | grep x");
+        prompt.lock().unwrap().insert_str(" | wc", EditKind::Typing);
+        assert!(frame(&mut lock).text().starts_with(" wc "));
+    }
+
+    #[test]
+    fn gradients_go_end_to_end() {
+        let gradient = |coverage| {
+            Color::Gradient(Box::new(Gradient::new(
+                vec![
+                    (HexColor { r: 0, g: 0, b: 0 }, None),
+                    (
+                        HexColor {
+                            r: 90,
+                            g: 90,
+                            b: 90,
+                        },
+                        None,
+                    ),
+                ],
+                Interpolation::Rgb,
+                coverage,
+            )))
+        };
+        let visual_state = VisualState {
+            padding: 2,
+            bg_color: gradient(Coverage::Padding),
+            color: gradient(Coverage::Content),
+            ..Default::default()
+        };
+        let chain = chain_with(20, vec![link(0.0, element("abcd", visual_state))]);
+        let grid = frame(&mut chain.lock().unwrap());
+
+        let gray = |v| ctColor::Rgb { r: v, g: v, b: v };
+        let bg: Vec<ctColor> = (0..8).map(|c| grid.cell(c, 0).unwrap().style.bg).collect();
+        let fg: Vec<ctColor> = (0..8).map(|c| grid.cell(c, 0).unwrap().style.fg).collect();
+        // the background goes across the padding too, the text only across itself
+        assert_eq!(bg, [0, 13, 26, 39, 51, 64, 77, 90].map(gray));
+        assert_eq!(fg, [0, 0, 0, 30, 60, 90, 90, 90].map(gray));
+    }
+
+    #[test]
+    fn wide_characters_take_two_columns() {
+        let (s, start, end) = pad_string("😄x".to_string(), 7, &Alignment::Center);
+        assert_eq!((s.as_str(), start, end), ("  😄x  ", 2, 5));
+        let (s, start, end) = pad_string("日本".to_string(), 6, &Alignment::Right);
+        assert_eq!((s.as_str(), start, end), ("  日本", 2, 6));
+
+        assert_eq!(truncate_to_width("a日本", 4), "a日");
+        assert_eq!(truncate_to_width("a日本", 2), "a");
+        // half of 日 is off the edge, so what's left of it is blank
+        assert_eq!(visible_graphemes("a日b", 2), [(2, " "), (3, "b")]);
+        assert_eq!(visible_graphemes("a日b", 1), [(1, "日"), (3, "b")]);
+
+        // the springs need to know how wide it actually is on screen
+        let visual_state = VisualState {
+            width: Width::Units(4),
+            ..Default::default()
+        };
+        let chain = chain_with(20, vec![link(-1.0, element("😄😄😄", visual_state))]);
+        let mut lock = chain.lock().unwrap();
+        let grid = frame(&mut lock);
+        assert_eq!(lock.links[0].mass.width, 4);
+        assert_eq!(grid.text(), "😄");
+    }
+
+    #[test]
+    fn the_right_chain_squeezes_up_too() {
+        let prompt = ElementType::Prompt(Arc::new(Mutex::new(Prompt::default())));
+        let chain = chain_with(40, vec![link(2.0, prompt)]);
+        let mut lock = chain.lock().unwrap();
+        let visual_state = VisualState {
+            overflow: Overflow::Ellipsis,
+            ..Default::default()
+        };
+        let path = element("~/a/very/long/path/to/somewhere/deep", visual_state);
+        lock.right = Some(SideChain {
+            spring: lock.spring.clone(),
+            links: vec![link(20.0, path)],
+            hidden: 0,
+        });
+        let grid = frame(&mut lock);
+
+        // 40 columns, less the input's start, the 20 it's left & the margin, less the spacing
+        let right = lock.right.as_ref().unwrap();
+        assert_eq!(right.links[0].mass.width, 16);
+        assert!(grid.text().ends_with('…'), "{:?}", grid.text());
+    }
+
+    #[test]
+    fn lowest_priority_gives_up_room_first() {
+        let vs = |overflow, priority| VisualState {
+            overflow,
+            priority,
+            ..Default::default()
+        };
+        let hide = vs(Overflow::Hide, 0);
+        let ellipsis = vs(Overflow::Ellipsis, 0);
+        let clip = vs(Overflow::Clip, -1);
+        let wanted = [
+            (8, Some(&ellipsis)),
+            (6, Some(&hide)),
+            (10, Some(&clip)),
+            (0, None),
+        ];
+
+        // everything fits
+        assert_eq!(fit(&wanted, 1, 27), vec![Fit::Whole; 4]);
+        // a tie goes to the one further right, clipping never gives anything up
+        assert_eq!(
+            fit(&wanted, 1, 24),
+            vec![Fit::Whole, Fit::Hidden, Fit::Whole, Fit::Whole]
+        );
+        // then the ellipsis gets cut, but never past a column of content
+        assert_eq!(
+            fit(&wanted, 1, 18),
+            vec![Fit::Cut(6), Fit::Hidden, Fit::Whole, Fit::Whole]
+        );
+        assert_eq!(
+            fit(&wanted, 1, 0),
+            vec![Fit::Cut(3), Fit::Hidden, Fit::Whole, Fit::Whole]
+        );
+
+        let ellipsis = vs(Overflow::Ellipsis, 0);
+        assert_eq!(layout(&ellipsis, "~/code/eash", Some(7)).0, " ~/co… ");
+        let shrink = VisualState {
+            width: Width::Units(8),
+            align: Alignment::Right,
+            ..vs(Overflow::Shrink, 0)
+        };
+        assert_eq!(layout(&shrink, "ok", None).0, "     ok ");
+        assert_eq!(layout(&shrink, "ok", Some(5)).0, "  ok ");
+    }
+
+    #[test]
+    fn caps_blend_into_their_neighbours() {
+        let capped = |content: &str, bg: HexColor, left_cap, right_cap| {
+            let visual_state = VisualState {
+                bg_color: Color::Solid(bg),
+                left_cap,
+                right_cap,
+                ..Default::default()
+            };
+            element(content, visual_state)
+        };
+        let red = HexColor { r: 200, g: 0, b: 0 };
+        let blue = HexColor { r: 0, g: 0, b: 200 };
+        let chain = chain_with(
+            20,
+            vec![
+                link(0.0, capped("a", red, Cap::None, Cap::Powerline)),
+                link(4.0, capped("b", blue, Cap::Rounded, Cap::Rounded)),
+            ],
+        );
+        let mut lock = chain.lock().unwrap();
+        lock.spring.spacing = 0;
+        let (red, blue) = (
+            ctColor::Rgb { r: 200, g: 0, b: 0 },
+            ctColor::Rgb { r: 0, g: 0, b: 200 },
+        );
+
+        // (the first frame is just to work out how wide everything is)
+        frame(&mut lock);
+        let grid = frame(&mut lock);
+        // touching: the arrow points right into b, and b's own cap gets out of the way
+        assert_eq!(grid.text(), " a \u{e0b0}  b \u{e0b4}");
+        assert_eq!(grid.cell(3, 0).unwrap().style, Style::new(red, blue));
+        assert_eq!(grid.cell(4, 0).unwrap().style.bg, blue);
+        assert_eq!(
+            grid.cell(8, 0).unwrap().style,
+            Style::new(blue, ctColor::Reset)
+        );
+
+        // a gap: both caps sit on the terminal's background
+        lock.links[1].mass.position = 6.0;
+        let grid = frame(&mut lock);
+        assert_eq!(grid.text(), " a \u{e0b0}  \u{e0b6} b \u{e0b4}");
+        assert_eq!(
+            grid.cell(3, 0).unwrap().style,
+            Style::new(red, ctColor::Reset)
+        );
+        assert_eq!(
+            grid.cell(6, 0).unwrap().style,
+            Style::new(blue, ctColor::Reset)
+        );
+    }
 }
@@ -0,0 +1,96 @@
+use crate::error::EASHError;
+use std::{
+    collections::hash_map::RandomState,
+    env,
+    fs::{self, File, OpenOptions},
+    hash::BuildHasher,
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    process::Command,
+};
+
+// $VISUAL, then $EDITOR, then vi because it's always there
+pub fn editor_command() -> Vec<String> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|e| !e.trim().is_empty())
+        .unwrap_or("vi".to_string());
+    // so things like "code --wait" work
+    editor.split_whitespace().map(str::to_string).collect()
+}
+
+// gets deleted however we leave edit_externally
+struct TempFile(PathBuf);
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        _ = fs::remove_file(&self.0);
+    }
+}
+
+// a brand new file with a name nobody could guess, that only we can read. anything already sitting
+// there (like a symlink somebody left lying around for us) means try another name
+fn create_temp_file() -> Result<(TempFile, File), EASHError> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    for _ in 0..100 {
+        // (RandomState is seeded randomly, good enough for a file name)
+        let suffix = RandomState::new().hash_one(std::process::id());
+        let path = env::temp_dir().join(format!("eash-edit-{suffix:016x}.sh"));
+        match options.open(&path) {
+            Ok(file) => return Ok((TempFile(path), file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Err(std::io::Error::from(ErrorKind::AlreadyExists).into())
+}
+
+// hands the text to the user's editor in a temp file and gives back what they left in it,
+// or None if the editor didn't exit happily. the terminal needs to be out of raw mode before this!
+pub fn edit_externally(text: &str) -> Result<Option<String>, EASHError> {
+    let (temp, mut file) = create_temp_file()?;
+    file.write_all(text.as_bytes())?;
+    drop(file);
+
+    let command = editor_command();
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(&temp.0)
+        .status()?;
+    if !status.success() {
+        return Ok(None);
+    }
+
+    // editors like to stick a newline on the end
+    let edited = fs::read_to_string(&temp.0)?;
+    Ok(Some(edited.trim_end_matches(['\n', '\r']).to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::create_temp_file;
+    use std::fs;
+
+    #[test]
+    fn temp_files_are_new_private_and_cleaned_up() {
+        let (first, _) = create_temp_file().unwrap();
+        let (second, _) = create_temp_file().unwrap();
+        assert_ne!(first.0, second.0);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&first.0).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let path = first.0.clone();
+        drop(first);
+        assert!(!path.exists());
+    }
+}
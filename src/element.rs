@@ -1,12 +1,16 @@
 use crate::{
+    evaluate::Token,
+    keymap::Action,
     misc_types::{Direction, Glyph, VisualState},
     prompt::Prompt,
+    vi::ViMode,
 };
 use std::sync::{Arc, Mutex};
 
 pub struct BasicElement {
     pub visual_state: VisualState,
     pub content: String,
+    pub on_click: Option<Action>,
 }
 
 pub struct ElementWithGlyph {
@@ -16,8 +20,90 @@ pub struct ElementWithGlyph {
     pub direction: Direction,
 }
 
+// looks like a different basic element depending on what vi mode the prompt is in
+pub struct ModeIndicator {
+    pub insert: BasicElement,
+    pub normal: BasicElement,
+    pub visual: BasicElement,
+    pub prompt: Arc<Mutex<Prompt>>,
+    pub on_click: Option<Action>,
+}
+
+impl ModeIndicator {
+    pub fn element_for(&self, mode: Option<ViMode>) -> &BasicElement {
+        match mode {
+            Some(ViMode::Normal) => &self.normal,
+            Some(ViMode::Visual) => &self.visual,
+            // no vi mode means you're always typing
+            Some(ViMode::Insert) | None => &self.insert,
+        }
+    }
+}
+
+// what a prompt trigger gets to look at
+pub struct PromptUpdate<'a> {
+    pub text: &'a str,
+    pub tokens: &'a [Token],
+    pub completions: Option<&'a str>,
+}
+
+// works out what an element should say from the prompt
+pub type PromptTrigger = Box<dyn Fn(&PromptUpdate) -> String + Send>;
+
+// a basic element whose content gets redone every time the prompt's text changes
+// (TriggerType::PromptUpdate), from the tokens the prompt already keeps around
+pub struct ReactiveElement {
+    pub element: BasicElement,
+    pub prompt: Arc<Mutex<Prompt>>,
+    pub trigger: PromptTrigger,
+    // the prompt revision the content was last worked out for
+    pub revision: Option<u64>,
+}
+
+impl ReactiveElement {
+    pub fn new(element: BasicElement, prompt: Arc<Mutex<Prompt>>, trigger: PromptTrigger) -> Self {
+        ReactiveElement {
+            element,
+            prompt,
+            trigger,
+            revision: None,
+        }
+    }
+
+    // runs the trigger if the text changed since last time. false if the prompt was busy
+    pub fn update(&mut self) -> bool {
+        let Ok(prompt) = self.prompt.try_lock() else {
+            return false;
+        };
+        if self.revision != Some(prompt.revision) {
+            self.element.content = (self.trigger)(&PromptUpdate {
+                text: prompt.text(),
+                tokens: prompt.tokens(),
+                completions: prompt.completions(),
+            });
+            self.revision = Some(prompt.revision);
+        }
+        true
+    }
+}
+
 pub enum ElementType {
     BasicElement(BasicElement),
     // ElementWithGlyph(ElementWithGlyph),
+    // (boxed, it's three elements in one)
+    ModeIndicator(Box<ModeIndicator>),
+    Reactive(Box<ReactiveElement>),
     Prompt(Arc<Mutex<Prompt>>),
 }
+
+impl ElementType {
+    // what to do when it gets clicked on
+    pub fn on_click(&self) -> Option<Action> {
+        match self {
+            ElementType::BasicElement(e) => e.on_click,
+            ElementType::ModeIndicator(m) => m.on_click,
+            ElementType::Reactive(r) => r.element.on_click,
+            ElementType::Prompt(_) => None,
+        }
+    }
+}
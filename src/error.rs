@@ -11,11 +11,14 @@ pub enum EASHError {
     ConfigInvalidType { expected: &'static str, got: String },
     ConfigPromptUsed,
     ConfigInvalidGlyph(String),
-    CommandStartedWithoutProgram(Token)
+    ConfigInvalidKey(String),
+    ConfigInvalidAction(String),
+    CommandStartedWithoutProgram(Token),
+    InvalidArgument(String),
 }
 
 impl From<std::io::Error> for EASHError {
     fn from(error: std::io::Error) -> EASHError {
         EASHError::IOError(error)
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 use crate::error::EASHError;
+use std::{iter::Peekable, str::CharIndices};
 
 // NOTICE: i really want to replace ALL of this code with a competent and actually usable syntax so just like wait ig
 #[derive(PartialEq, Debug, Clone)]
@@ -67,121 +68,133 @@ fn str_to_token(s: &str, mode: &ConsumptionMode, st: usize, en: usize) -> Token
 }
 
 // notice: i aint got no langdev experience pls hold the tomatoes
-// also i don't like that i made tokenization just one fatass fucking function
-pub fn tokenize(s: &str) -> Vec<Token> {
-    // the sizes were random numbers i chose...
-    let mut tokens = Vec::with_capacity(5);
-    let mut current_token = String::with_capacity(20);
-    let mut current_token_start = 0;
+// hands out one token at a time. every token starts from a clean slate (no half finished strings or
+// anything), which is what lets Prompt re-tokenize from the middle of a line.
+// spans are byte ranges [start, end) covering the token exactly as it was typed, quotes & dashes included
+pub struct Tokenizer<'a> {
+    s: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+    offset: usize,
+}
 
-    let mut mode = ConsumptionMode::Default;
-    let mut chars = s.chars().enumerate().peekable();
-    loop {
-        let c;
-        let pos;
-        if let Some((np, nc)) = chars.next() {
-            c = nc;
-            pos = np;
-        } else {
-            break;
-        };
+impl<'a> Tokenizer<'a> {
+    // from has to be the start of a token (or some whitespace before one)
+    pub fn new(s: &'a str, from: usize) -> Tokenizer<'a> {
+        Tokenizer {
+            s,
+            chars: s[from..].char_indices().peekable(),
+            offset: from,
+        }
+    }
+}
 
-        // idk how i should mutate mode here so for now you're just supposed to mutate it after using this...
-        // ):
-        let mut push_token = |mode, current_token: &mut String, pos: usize| {
-            tokens.push(str_to_token(current_token, mode, current_token_start, pos));
-            current_token_start = pos + 1;
-            current_token.clear();
-        };
+impl Iterator for Tokenizer<'_> {
+    type Item = Token;
 
-        let mut string_check = |mode: &ConsumptionMode| {
-            // channeling my inner yanderedev....
-            // the character should never be an ending string so we SHOULD be good....
-            if let ConsumptionMode::String(_) = mode {
-                current_token.push(c);
-                return true;
-            }
-            false
-        };
+    fn next(&mut self) -> Option<Token> {
+        while self.chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+        let (start, first) = self.chars.next()?;
+        let start = start + self.offset;
 
-        match c {
-            ' ' => {
-                // if we're not in a string then tokenize, otherwise string stuff happens...
-                if !string_check(&mode) && !current_token.is_empty() {
-                    push_token(&mode, &mut current_token, pos);
-                    mode = ConsumptionMode::Default;
-                }
-            }
-            '-' => {
-                if let Some((_, '-')) = chars.peek() {
-                    _ = chars.next();
-                    mode = ConsumptionMode::DoubleFlag;
-                } else {
-                    mode = ConsumptionMode::Flag;
-                }
-            }
-            '"' | '\'' | '`' => {
-                // with this implementation i can EASILY add as many string indicators as i want... i cooked!
-                if let ConsumptionMode::String(sc) = mode {
-                    if c == sc {
-                        push_token(&mode, &mut current_token, pos);
-                        mode = ConsumptionMode::Default;
-                        continue;
-                    }
-                } else {
-                    mode = ConsumptionMode::String(c);
-                }
-            }
+        let mut mode = ConsumptionMode::Default;
+        let mut content = String::new();
+        match first {
             '|' => {
-                if string_check(&mode) {
-                    continue;
-                }
-                if !current_token.is_empty() {
-                    push_token(&mode, &mut current_token, pos - 1);
-                }
-                mode = ConsumptionMode::Default;
-                tokens.push(Token {
-                    start: current_token_start,
-                    end: pos,
+                return Some(Token {
+                    start,
+                    end: start + 1,
                     contents: TokenType::Pipe,
                 });
-                current_token_start = pos + 1;
             }
-            '&' if matches!(chars.peek(), Some((_, '&'))) => {
-                // functionally just &&
-                if string_check(&mode) {
-                    continue;
-                }
-                if !current_token.is_empty() {
-                    push_token(&mode, &mut current_token, pos - 1);
-                }
-                _ = chars.next();
-                mode = ConsumptionMode::Default;
-                tokens.push(Token {
-                    start: current_token_start,
-                    end: pos + 1,
+            // functionally just &&
+            '&' if self.chars.next_if(|(_, c)| *c == '&').is_some() => {
+                return Some(Token {
+                    start,
+                    end: start + 2,
                     contents: TokenType::AndThen,
                 });
-                current_token_start = pos + 2;
             }
-            _ => {
-                if string_check(&mode) {
-                    continue;
+            // a lone - or -- still counts as a flag, completions need to know about it
+            '-' if self.chars.next_if(|(_, c)| *c == '-').is_some() => {
+                mode = ConsumptionMode::DoubleFlag
+            }
+            '-' => mode = ConsumptionMode::Flag,
+            // with this implementation i can EASILY add as many string indicators as i want... i cooked!
+            '"' | '\'' | '`' => mode = ConsumptionMode::String(first),
+            _ => content.push(first),
+        }
+
+        while let Some(&(i, c)) = self.chars.peek() {
+            let position = i + self.offset;
+            match (&mode, c) {
+                (ConsumptionMode::String(q), c) if c == *q => {
+                    self.chars.next();
+                    return Some(str_to_token(&content, &mode, start, position + 1));
                 }
-                current_token.push(c);
+                (ConsumptionMode::String(_), c) => content.push(c),
+                (_, '\'') if is_apostrophe(content.chars().last()) => content.push(c),
+                (_, c) if c.is_whitespace() || c == '|' || self.s[position..].starts_with("&&") => {
+                    return Some(str_to_token(&content, &mode, start, position));
+                }
+                (_, '"' | '\'' | '`') => mode = ConsumptionMode::String(c),
+                _ => content.push(c),
             }
+            self.chars.next();
         }
+
+        // ran out of line (which might mean an unfinished string)
+        Some(str_to_token(&content, &mode, start, self.s.len()))
     }
-    if !current_token.is_empty() {
-        tokens.push(str_to_token(
-            &current_token,
-            &mode,
-            current_token_start,
-            s.len() - 1,
-        ));
-    }
+}
+
+// a ' straight after a letter is an apostrophe (echo don't), not the start of a string
+fn is_apostrophe(previous: Option<char>) -> bool {
+    previous.is_some_and(|p| p.is_alphanumeric())
+}
 
+// what the command being typed right now is: the first token after the last | or &&
+pub fn current_command(tokens: &[Token]) -> Option<&str> {
+    let start = tokens
+        .iter()
+        .rposition(|t| matches!(t.contents, TokenType::Pipe | TokenType::AndThen))
+        .map_or(0, |i| i + 1);
     tokens
+        .get(start)?
+        .contents
+        .not_a_symbol()
+        .map(String::as_str)
+}
+
+pub fn tokenize(s: &str) -> Vec<Token> {
+    Tokenizer::new(s, 0).collect()
+}
+
+// whether enter should keep going onto another line instead of running this:
+// an unclosed quote or bracket, or a line ending in | or &&
+pub fn is_incomplete(s: &str) -> bool {
+    let mut quote = None;
+    let mut depth = 0i32;
+    let mut chars = s.chars();
+    let mut previous = None;
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\\') => _ = chars.next(),
+            (None, '\'') if is_apostrophe(previous) => {}
+            (None, '"' | '\'' | '`') => quote = Some(c),
+            (None, '(' | '[' | '{') => depth += 1,
+            (None, ')' | ']' | '}') => depth -= 1,
+            _ => {}
+        }
+        previous = Some(c);
+    }
+    if quote.is_some() || depth > 0 {
+        return true;
+    }
+
+    let trimmed = s.trim_end();
+    trimmed.ends_with('|') || trimmed.ends_with("&&") || trimmed.ends_with('\\')
 }
 
 struct TreeCommand {
@@ -189,21 +202,20 @@ struct TreeCommand {
     flags: Vec<(String, Option<String>)>,
     values: Vec<String>,
     pipe: bool, // will pipe this command to the next if it should (|) and not if it shouldnt (&& / nothing i guess)
-    next: Option<Box<TreeCommand>>
+    next: Option<Box<TreeCommand>>,
 }
 
 fn new_treecommand_with_token(t: &Token) -> Result<TreeCommand, EASHError> {
     if let Some(s) = t.contents.not_a_symbol() {
-        return Ok(
-            TreeCommand {
-                // im cloning up a STORM!!!
-                program_path: s.clone(),
-                flags: Vec::new(),
-                values: Vec::new(),
-                pipe: false,
-                next: None
-            })
-    }  else {
+        return Ok(TreeCommand {
+            // im cloning up a STORM!!!
+            program_path: s.clone(),
+            flags: Vec::new(),
+            values: Vec::new(),
+            pipe: false,
+            next: None,
+        });
+    } else {
         return Err(EASHError::CommandStartedWithoutProgram(t.clone()));
     }
 }
@@ -215,14 +227,12 @@ fn to_ast(tokens: &Vec<Token>) -> Result<Vec<TreeCommand>, EASHError> {
     loop {
         let t = match tokens_iter.next() {
             Some(t) => t,
-            None => {
-                break
-            },
+            None => break,
         };
         match &mut processing {
             None => {
                 processing = Some(new_treecommand_with_token(t)?);
-            },
+            }
             Some(p) => {
                 match &t.contents {
                     TokenType::Flag(s) => {
@@ -231,7 +241,7 @@ fn to_ast(tokens: &Vec<Token>) -> Result<Vec<TreeCommand>, EASHError> {
                             None => {
                                 // assume it's an empty flag
                                 p.flags.push((s.clone(), None));
-                            },
+                            }
                             Some(t2) => {
                                 if let Some(s2) = t2.contents.not_a_symbol() {
                                     p.flags.push((s.clone(), Some(s2.clone())));
@@ -240,18 +250,18 @@ fn to_ast(tokens: &Vec<Token>) -> Result<Vec<TreeCommand>, EASHError> {
                                 }
                             }
                         }
-                    },
+                    }
                     TokenType::Value(s) | TokenType::String(s) | TokenType::Directory(s) => {
                         p.values.push(s.clone());
-                    },
+                    }
                     TokenType::AndThen => {
                         commands.push(processing.unwrap()); // if you do a && | the program will explode....
                         processing = None;
-                    },
+                    }
                     TokenType::Pipe => {
                         commands.push(processing.unwrap());
                         processing = None;
-                    },
+                    }
                 }
             }
         }
@@ -262,41 +272,43 @@ fn to_ast(tokens: &Vec<Token>) -> Result<Vec<TreeCommand>, EASHError> {
 
 #[cfg(test)]
 mod tests {
-    use crate::evaluate::{Token, TokenType, tokenize};
+    use crate::evaluate::{Token, TokenType, current_command, is_incomplete, tokenize};
 
     #[test]
     fn tokenize_pipes_and_strings() {
         let command = "echo \"Hello, Air Jordans...\" | cat && clear";
+        // spans are [start, end) and cover the whole token, quotes included. bare words are Values,
+        // str_to_token only makes Strings out of quoted things
         let expected = vec![
             Token {
                 start: 0,
                 end: 4,
-                contents: TokenType::String("echo".to_string()),
+                contents: TokenType::Value("echo".to_string()),
             },
             Token {
                 start: 5,
-                end: 27,
+                end: 28,
                 contents: TokenType::String("Hello, Air Jordans...".to_string()),
             },
             Token {
-                start: 28,
-                end: 29,
+                start: 29,
+                end: 30,
                 contents: TokenType::Pipe,
             },
             Token {
-                start: 30,
+                start: 31,
                 end: 34,
-                contents: TokenType::String("cat".to_string()),
+                contents: TokenType::Value("cat".to_string()),
             },
             Token {
                 start: 35,
-                end: 35,
+                end: 37,
                 contents: TokenType::AndThen,
             },
             Token {
-                start: 36,
-                end: 42,
-                contents: TokenType::String("clear".to_string()),
+                start: 38,
+                end: 43,
+                contents: TokenType::Value("clear".to_string()),
             },
         ];
 
@@ -319,4 +331,31 @@ mod tests {
 
     //     assert_eq!(actual, expected);
     // }
+
+    #[test]
+    fn current_command_is_after_the_last_pipe() {
+        assert_eq!(current_command(&tokenize("git status")), Some("git"));
+        assert_eq!(current_command(&tokenize("ls -la | grep x")), Some("grep"));
+        assert_eq!(current_command(&tokenize("make && ")), None);
+        assert_eq!(current_command(&tokenize("")), None);
+    }
+
+    #[test]
+    fn incomplete_commands() {
+        assert!(is_incomplete("echo \"hello"));
+        assert!(is_incomplete("ls |"));
+        assert!(is_incomplete("make && "));
+        assert!(is_incomplete("if x {\n  echo"));
+        assert!(is_incomplete("echo one \\"));
+        assert!(!is_incomplete("echo \"a | b\""));
+        assert!(!is_incomplete("echo '{' \\( done"));
+        assert!(!is_incomplete("ls -la"));
+        // apostrophes aren't quotes
+        assert!(!is_incomplete("echo don't"));
+        assert!(is_incomplete("echo don't 'stop"));
+        assert_eq!(
+            tokenize("echo don't")[1].contents,
+            TokenType::Value("don't".to_string())
+        );
+    }
 }
@@ -0,0 +1,342 @@
+use crate::misc_types::HexColor;
+use serde::Deserialize;
+
+// which color space the stops get blended in. rgb is what we always did, oklab keeps the
+// brightness even the whole way across & hsl goes around the color wheel
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Interpolation {
+    #[default]
+    Rgb,
+    Oklab,
+    Hsl,
+}
+
+// how much of the element the gradient is stretched over. with Content the padding just gets
+// whatever color the ends are
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub enum Coverage {
+    #[default]
+    Padding,
+    Content,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct GradientStop {
+    pub color: HexColor,
+    // 0 is the start, 1 is the end
+    pub position: f32,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient {
+    // always at least two, sorted by position
+    pub stops: Vec<GradientStop>,
+    pub interpolation: Interpolation,
+    pub coverage: Coverage,
+}
+
+impl Gradient {
+    // stops without a position get spread out evenly, and a stop can't go before the one before it
+    pub fn new(
+        stops: Vec<(HexColor, Option<f32>)>,
+        interpolation: Interpolation,
+        coverage: Coverage,
+    ) -> Gradient {
+        let last = stops.len().saturating_sub(1).max(1) as f32;
+        let mut previous = 0.0f32;
+        let stops = stops
+            .into_iter()
+            .enumerate()
+            .map(|(i, (color, position))| {
+                let position = position.unwrap_or(i as f32 / last).clamp(previous, 1.0);
+                previous = position;
+                GradientStop { color, position }
+            })
+            .collect();
+        Gradient {
+            stops,
+            interpolation,
+            coverage,
+        }
+    }
+
+    // the color at t (0 to 1) along the gradient
+    pub fn sample(&self, t: f32) -> HexColor {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let first = &self.stops[0];
+        let last = &self.stops[self.stops.len() - 1];
+        if t <= first.position {
+            return first.color;
+        }
+        if t >= last.position {
+            return last.color;
+        }
+
+        let i = self.stops.partition_point(|s| s.position <= t);
+        let (from, to) = (&self.stops[i - 1], &self.stops[i]);
+        let span = to.position - from.position;
+        let t = if span <= 0.0 {
+            1.0
+        } else {
+            (t - from.position) / span
+        };
+        match self.interpolation {
+            Interpolation::Rgb => lerp_rgb(from.color, to.color, t),
+            Interpolation::Oklab => lerp_oklab(from.color, to.color, t),
+            Interpolation::Hsl => lerp_hsl(from.color, to.color, t),
+        }
+    }
+
+    // how far along the gradient cell i of an element is. content is the cells the text itself is in
+    pub fn position_of(&self, i: usize, cells: usize, content: (usize, usize)) -> f32 {
+        let (from, to) = match self.coverage {
+            Coverage::Padding => (0, cells),
+            Coverage::Content => content,
+        };
+        if to <= from + 1 {
+            return 0.0;
+        }
+        (i.saturating_sub(from) as f32 / (to - from - 1) as f32).min(1.0)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn to_channel(c: f32) -> u8 {
+    (c * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+fn lerp_rgb(a: HexColor, b: HexColor, t: f32) -> HexColor {
+    let channel = |a: u8, b: u8| lerp(a as f32, b as f32, t).round() as u8;
+    HexColor {
+        r: channel(a.r, b.r),
+        g: channel(a.g, b.g),
+        b: channel(a.b, b.b),
+    }
+}
+
+// https://bottosson.github.io/posts/oklab/ (the constants are copied straight from there)
+#[allow(clippy::excessive_precision)]
+fn to_oklab(c: HexColor) -> [f32; 3] {
+    let linear = |c: u8| {
+        let c = c as f32 / 255.0;
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let (r, g, b) = (linear(c.r), linear(c.g), linear(c.b));
+
+    let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+    let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+    let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+    [
+        0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+        1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+        0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+    ]
+}
+
+#[allow(clippy::excessive_precision)]
+fn from_oklab([lightness, a, b]: [f32; 3]) -> HexColor {
+    let l = (lightness + 0.3963377774 * a + 0.2158037573 * b).powi(3);
+    let m = (lightness - 0.1055613458 * a - 0.0638541728 * b).powi(3);
+    let s = (lightness - 0.0894841775 * a - 1.2914855480 * b).powi(3);
+
+    let gamma = |c: f32| {
+        if c <= 0.0031308 {
+            12.92 * c
+        } else {
+            1.055 * c.powf(1.0 / 2.4) - 0.055
+        }
+    };
+    HexColor {
+        r: to_channel(gamma(
+            4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s,
+        )),
+        g: to_channel(gamma(
+            -1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s,
+        )),
+        b: to_channel(gamma(
+            -0.0041960863 * l - 0.7034186147 * m + 1.7076147010 * s,
+        )),
+    }
+}
+
+fn lerp_oklab(a: HexColor, b: HexColor, t: f32) -> HexColor {
+    let (a, b) = (to_oklab(a), to_oklab(b));
+    from_oklab([0, 1, 2].map(|i| lerp(a[i], b[i], t)))
+}
+
+// hue in degrees, saturation & lightness 0 to 1
+fn to_hsl(c: HexColor) -> [f32; 3] {
+    let (r, g, b) = (c.r as f32 / 255.0, c.g as f32 / 255.0, c.b as f32 / 255.0);
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let lightness = (max + min) / 2.0;
+    let chroma = max - min;
+    if chroma == 0.0 {
+        return [0.0, 0.0, lightness];
+    }
+
+    let saturation = chroma / (1.0 - (2.0 * lightness - 1.0).abs());
+    let hue = if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    [hue * 60.0, saturation, lightness]
+}
+
+fn from_hsl([hue, saturation, lightness]: [f32; 3]) -> HexColor {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u8 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    HexColor {
+        r: to_channel(r + m),
+        g: to_channel(g + m),
+        b: to_channel(b + m),
+    }
+}
+
+fn lerp_hsl(a: HexColor, b: HexColor, t: f32) -> HexColor {
+    let (mut a, mut b) = (to_hsl(a), to_hsl(b));
+    // grays don't really have a hue, so borrow the other end's instead of spinning through red
+    if a[1] == 0.0 {
+        a[0] = b[0];
+    }
+    if b[1] == 0.0 {
+        b[0] = a[0];
+    }
+    // the short way around the wheel
+    let mut turn = b[0] - a[0];
+    if turn > 180.0 {
+        turn -= 360.0;
+    } else if turn < -180.0 {
+        turn += 360.0;
+    }
+    from_hsl([a[0] + turn * t, lerp(a[1], b[1], t), lerp(a[2], b[2], t)])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::ConfigColor,
+        gradient::{Coverage, Gradient, Interpolation},
+        misc_types::{Color, HexColor},
+    };
+
+    fn hex(r: u8, g: u8, b: u8) -> HexColor {
+        HexColor { r, g, b }
+    }
+
+    fn two(from: HexColor, to: HexColor, interpolation: Interpolation) -> Gradient {
+        Gradient::new(
+            vec![(from, None), (to, None)],
+            interpolation,
+            Coverage::Padding,
+        )
+    }
+
+    #[test]
+    fn blending() {
+        let (red, blue) = (hex(255, 0, 0), hex(0, 0, 255));
+        for interpolation in [Interpolation::Rgb, Interpolation::Oklab, Interpolation::Hsl] {
+            let g = two(red, blue, interpolation);
+            assert_eq!(g.sample(0.0), red, "{interpolation:?}");
+            assert_eq!(g.sample(1.0), blue, "{interpolation:?}");
+        }
+        assert_eq!(
+            two(red, blue, Interpolation::Rgb).sample(0.5),
+            hex(128, 0, 128)
+        );
+        // red to blue the short way is through magenta, at full brightness
+        assert_eq!(
+            two(red, blue, Interpolation::Hsl).sample(0.5),
+            hex(255, 0, 255)
+        );
+        // oklab stays brighter in the middle than plain rgb does
+        let middle = two(red, blue, Interpolation::Oklab).sample(0.5);
+        assert!(middle.r > 128 && middle.b > 128, "{middle:?}");
+    }
+
+    #[test]
+    fn stops() {
+        let g = Gradient::new(
+            vec![
+                (hex(0, 0, 0), None),
+                (hex(100, 100, 100), Some(0.8)),
+                (hex(200, 200, 200), Some(0.5)),
+            ],
+            Interpolation::Rgb,
+            Coverage::Padding,
+        );
+        // the last stop can't go backwards, so it ends up on top of the middle one
+        assert_eq!(g.stops[2].position, 0.8);
+        assert_eq!(g.sample(0.4), hex(50, 50, 50));
+        assert_eq!(g.sample(0.9), hex(200, 200, 200));
+    }
+
+    #[test]
+    fn coverage() {
+        let mut g = two(hex(255, 0, 0), hex(0, 0, 255), Interpolation::Rgb);
+        assert_eq!(g.position_of(0, 5, (1, 4)), 0.0);
+        assert_eq!(g.position_of(2, 5, (1, 4)), 0.5);
+        assert_eq!(g.position_of(4, 5, (1, 4)), 1.0);
+
+        g.coverage = Coverage::Content;
+        assert_eq!(g.position_of(0, 5, (1, 4)), 0.0);
+        assert_eq!(g.position_of(2, 5, (1, 4)), 0.5);
+        assert_eq!(g.position_of(3, 5, (1, 4)), 1.0);
+        assert_eq!(g.position_of(4, 5, (1, 4)), 1.0);
+    }
+
+    #[test]
+    fn config_forms() {
+        let parse = |s: &str| -> Result<Color, _> {
+            let config: ConfigColor = toml::from_str(s).unwrap();
+            config.try_into()
+        };
+
+        let Ok(Color::Gradient(old)) = parse(
+            "type = 'Gradient'\nfrom = {r = 0, g = 0, b = 0}\nto = {r = 255, g = 255, b = 255}",
+        ) else {
+            panic!("from/to should still work");
+        };
+        assert_eq!(
+            *old,
+            two(hex(0, 0, 0), hex(255, 255, 255), Interpolation::Rgb)
+        );
+
+        let Ok(Color::Gradient(new)) = parse(
+            "type = 'Gradient'\ninterpolation = 'Oklab'\ncover = 'Content'\n\
+             stops = [{r = 0, g = 0, b = 0}, {r = 9, g = 9, b = 9, position = 0.25}, {r = 1, g = 1, b = 1}]",
+        ) else {
+            panic!("stops should work");
+        };
+        assert_eq!(new.interpolation, Interpolation::Oklab);
+        assert_eq!(new.coverage, Coverage::Content);
+        let positions: Vec<f32> = new.stops.iter().map(|s| s.position).collect();
+        assert_eq!(positions, [0.0, 0.25, 1.0]);
+
+        assert!(parse("type = 'Gradient'\nstops = [{r = 0, g = 0, b = 0}]").is_err());
+        assert!(
+            parse("type = 'Gradient'\nstops = [{r = 0, g = 0, b = 0, position = nan}, {r = 1, g = 1, b = 1}]")
+                .is_err()
+        );
+    }
+}
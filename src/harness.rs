@@ -0,0 +1,358 @@
+// runs a whole chain without a terminal for the tests: keys go in, the springs get stepped with a
+// fixed dt, and frames come out of a GridSurface as text & colors to compare against snapshots/
+use crate::{
+    abbreviations::Abbreviations,
+    actions::{Edited, edit},
+    chain::{Chain, is_settled, step_links},
+    config::GlyphList,
+    draw::draw,
+    element::ElementType,
+    keymap::{Chord, KeyResult, Keymap},
+    kill_ring::KillRing,
+    misc_types::{Direction, EASHPallete, Spring},
+    prompt::Prompt,
+    surface::{GridSurface, Style},
+    vi::{ViConfig, ViResult, ViState},
+};
+use crossterm::event::{KeyCode, KeyEvent};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+pub const DT: f32 = 1.0 / 60.0;
+
+pub struct Harness {
+    pub prompt: Arc<Mutex<Prompt>>,
+    pub chain: Mutex<Chain>,
+    pub glyphs: GlyphList,
+    pub pallete: EASHPallete,
+    pub vi_config: ViConfig,
+    pub width: u16,
+    pub abbreviations: Abbreviations,
+    keymap: Keymap,
+    vi: ViState,
+    kill_ring: KillRing,
+}
+
+impl Harness {
+    // elements gets the prompt so mode indicators & such can hang onto it
+    pub fn new(
+        width: u16,
+        elements: impl FnOnce(&Arc<Mutex<Prompt>>) -> Vec<ElementType>,
+    ) -> Harness {
+        let prompt = Arc::new(Mutex::new(Prompt::default()));
+        let spring = Spring {
+            spacing: 1,
+            constant: 1.0,
+            dampening: 0.075,
+        };
+        let mut chain = Chain::new(spring, elements(&prompt), prompt.clone());
+        chain.columns = width;
+        Harness {
+            prompt,
+            chain: Mutex::new(chain),
+            glyphs: GlyphList::default(),
+            pallete: EASHPallete::default(),
+            vi_config: ViConfig::default(),
+            width,
+            abbreviations: Abbreviations::default(),
+            keymap: Keymap::new(&HashMap::new()).unwrap(),
+            vi: ViState::default(),
+            kill_ring: KillRing::default(),
+        }
+    }
+
+    // the same way main handles a key, minus anything that needs more than the prompt
+    // (history, completion, the kill ring actions...), those just get ignored
+    pub fn key(&mut self, event: KeyEvent) {
+        let mut prompt = self.prompt.lock().unwrap();
+        match self.vi.handle_key(&mut prompt, &mut self.kill_ring, &event) {
+            ViResult::Handled => return,
+            ViResult::Bump(direction) => {
+                self.chain.lock().unwrap().bump(30.0, direction);
+                return;
+            }
+            ViResult::PassThrough => {}
+        }
+
+        match self.keymap.feed(&event) {
+            KeyResult::Insert(c) => {
+                if self.abbreviations.type_character(&mut prompt, c) {
+                    self.chain.lock().unwrap().bump(20.0, Direction::Right);
+                }
+            }
+            KeyResult::Action(action) => {
+                if let Edited::Bump(velocity, direction) = edit(&mut prompt, action) {
+                    self.chain.lock().unwrap().bump(velocity, direction);
+                }
+            }
+            KeyResult::Pending | KeyResult::Unbound => {}
+        }
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    // keys written like in the config: "ctrl-a", "shift-left", "alt-enter"...
+    pub fn press(&mut self, keys: &str) {
+        for key in keys.split_whitespace() {
+            let chord = Chord::parse(key).unwrap();
+            self.key(KeyEvent::new(chord.code, chord.modifiers));
+        }
+    }
+
+    pub fn step(&mut self, frames: usize) {
+        // the springs need widths, which only get worked out by drawing
+        for _ in 0..frames {
+            self.render();
+            step_links(&mut self.chain.lock().unwrap(), DT);
+        }
+    }
+
+    // step until nothing's moving (or give up after a minute of frames)
+    pub fn settle(&mut self) {
+        for _ in 0..60 * 60 {
+            self.step(1);
+            if is_settled(&self.chain.lock().unwrap()) {
+                return;
+            }
+        }
+        panic!("the chain never settled");
+    }
+
+    pub fn render(&mut self) -> GridSurface {
+        let mut grid = GridSurface::new(self.width);
+        let mut chain = self.chain.lock().unwrap();
+        draw(
+            &mut grid,
+            &mut chain,
+            &self.glyphs,
+            &self.pallete,
+            &self.vi_config,
+        )
+        .unwrap();
+        grid
+    }
+
+    pub fn snapshot(&mut self) -> String {
+        snapshot(&self.render())
+    }
+}
+
+// the text, then the same again with a letter for each style (. is the terminal's own colors),
+// then which letter is which & where the cursor ended up
+pub fn snapshot(grid: &GridSurface) -> String {
+    let mut styles: Vec<Style> = Vec::new();
+    let mut colors = Vec::new();
+    for row in &grid.rows {
+        let line: String = row
+            .iter()
+            .map(|cell| {
+                if cell.style == Style::default() {
+                    return '.';
+                }
+                let index = styles
+                    .iter()
+                    .position(|s| *s == cell.style)
+                    .unwrap_or_else(|| {
+                        styles.push(cell.style);
+                        styles.len() - 1
+                    });
+                (b'a' + index as u8) as char
+            })
+            .collect();
+        colors.push(line.trim_end_matches('.').to_string());
+    }
+
+    let mut out = grid.text();
+    out.push_str("\n-- colors --\n");
+    out.push_str(&colors.join("\n"));
+    out.push_str("\n-- legend --\n");
+    for (i, style) in styles.iter().enumerate() {
+        out.push_str(&format!(
+            "{}: {:?} on {:?}\n",
+            (b'a' + i as u8) as char,
+            style.fg,
+            style.bg
+        ));
+    }
+    out.push_str(&format!("cursor: {:?}\n", grid.cursor));
+    out
+}
+
+// compares against src/snapshots/<name>.snap. run with EASH_UPDATE_SNAPSHOTS=1 to (re)write them
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("src/snapshots")
+        .join(format!("{name}.snap"));
+    if env::var_os("EASH_UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "no snapshot at {}, run with EASH_UPDATE_SNAPSHOTS=1 to make one",
+            path.display()
+        )
+    });
+    assert_eq!(expected, actual, "snapshot {name} changed");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        chain::SideChain,
+        element::{BasicElement, ElementType},
+        harness::{Harness, assert_snapshot},
+        misc_types::{Color, HexColor, Overflow, VisualState},
+    };
+
+    fn element(content: &str, r: u8, g: u8, b: u8) -> ElementType {
+        ElementType::BasicElement(BasicElement {
+            visual_state: VisualState {
+                bg_color: Color::Solid(HexColor { r, g, b }),
+                ..Default::default()
+            },
+            content: content.to_string(),
+            on_click: None,
+        })
+    }
+
+    fn harness() -> Harness {
+        Harness::new(60, |_| {
+            vec![element("eash", 56, 90, 90), element("~/code", 90, 56, 90)]
+        })
+    }
+
+    #[test]
+    fn typing_settles_into_place() {
+        let mut h = harness();
+        h.settle();
+        h.type_text("echo \"hi (there)\" | cat -n");
+        h.press("left left left left");
+        h.settle();
+        assert_snapshot("typing_settles_into_place", &h.snapshot());
+    }
+
+    #[test]
+    fn selecting_across_lines() {
+        let mut h = harness();
+        h.settle();
+        h.type_text("ls");
+        h.press("alt-enter");
+        h.type_text("  && echo done");
+        h.press("shift-left shift-left shift-left shift-left");
+        h.settle();
+        assert_snapshot("selecting_across_lines", &h.snapshot());
+    }
+
+    #[test]
+    fn abbreviations_flash_after_the_space() {
+        let mut h = harness();
+        h.abbreviations
+            .list
+            .insert("gco".to_string(), "git checkout".to_string());
+        h.settle();
+        h.type_text("gco main");
+        assert_eq!(h.prompt.lock().unwrap().text(), "git checkout main");
+        // (the space that set it off didn't clear it again)
+        let expanded = h.prompt.lock().unwrap().expanded;
+        assert_eq!(expanded.map(|(start, end, _)| (start, end)), Some((0, 12)));
+
+        // (straight away, the flash doesn't last long)
+        let grid = h.render();
+        let start = grid.text().find("git").unwrap() as u16;
+        let flash = h.pallete.abbreviation_bg.to_color_for_char(0.0);
+        assert_eq!(grid.cell(start, 0).unwrap().style.bg, flash);
+        assert_ne!(grid.cell(start + 13, 0).unwrap().style.bg, flash);
+    }
+
+    #[test]
+    fn bumping_the_wall_shoves_the_prompt() {
+        let mut h = harness();
+        h.settle();
+        h.type_text("abc");
+        h.press("home");
+        h.settle();
+        // going left from the very start hits the wall, a few frames later it's still flying
+        h.press("left");
+        h.step(5);
+        assert_snapshot("bumping_the_wall_shoves_the_prompt", &h.snapshot());
+    }
+
+    #[test]
+    fn right_chain_makes_room() {
+        let mut h = harness();
+        {
+            let mut chain = h.chain.lock().unwrap();
+            let spring = chain.spring.clone();
+            let elements = vec![element("12:00", 90, 90, 56), element("main", 56, 56, 90)];
+            chain.right = SideChain::new(spring, elements);
+        }
+        h.settle();
+        h.type_text("echo short");
+        h.settle();
+        let roomy = h.snapshot();
+
+        // running into it pushes the clock out of the way first, then the branch
+        h.type_text(" and then a bit longer");
+        h.settle();
+        assert_snapshot("right_chain_at_rest", &roomy);
+        assert_snapshot("right_chain_makes_room", &h.snapshot());
+    }
+
+    #[test]
+    fn above_chain_gets_its_own_line() {
+        let mut h = harness();
+        {
+            let mut chain = h.chain.lock().unwrap();
+            let spring = chain.spring.clone();
+            chain.above = SideChain::new(spring, vec![element("~/code/eash", 56, 90, 56)]);
+        }
+        h.settle();
+        h.type_text("ls");
+        h.press("alt-enter");
+        h.type_text("pwd");
+        h.settle();
+        assert_snapshot("above_chain_gets_its_own_line", &h.snapshot());
+    }
+
+    #[test]
+    fn narrow_terminal_squeezes_the_chain() {
+        let squeezable = |content: &str, overflow, priority| {
+            ElementType::BasicElement(BasicElement {
+                visual_state: VisualState {
+                    bg_color: Color::Solid(HexColor {
+                        r: 56,
+                        g: 90,
+                        b: 90,
+                    }),
+                    overflow,
+                    priority,
+                    ..Default::default()
+                },
+                content: content.to_string(),
+                on_click: None,
+            })
+        };
+        let mut h = Harness::new(32, |_| {
+            vec![
+                squeezable("~/code/eash/src", Overflow::Ellipsis, 1),
+                squeezable("main", Overflow::Hide, 0),
+            ]
+        });
+        h.settle();
+        // the branch goes first, then the path gets cut down, then the input scrolls to keep up
+        h.type_text("echo one two three four five");
+        h.settle();
+        assert_snapshot("narrow_terminal_squeezes_the_chain", &h.snapshot());
+    }
+}
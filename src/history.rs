@@ -0,0 +1,132 @@
+use crate::evaluate::tokenize;
+
+#[derive(Default)]
+pub struct History {
+    pub entries: Vec<String>,
+    // (start, end, how many commands back) of the last alt+. so pressing it again can go further back
+    last_argument: Option<(usize, usize, usize)>,
+    // where we are while going through history with up/down, and whatever was typed before we started
+    browsing: Option<(usize, String)>,
+}
+
+// the last word of a command, exactly as it was typed (quotes and all)
+fn last_argument_of(line: &str) -> Option<&str> {
+    let last = tokenize(line).pop()?;
+    let argument = line.get(last.start..last.end)?.trim();
+    (!argument.is_empty()).then_some(argument)
+}
+
+impl History {
+    pub fn push(&mut self, line: &str) {
+        self.browsing = None;
+        let line = line.trim();
+        if line.is_empty() || self.entries.last().is_some_and(|l| l == line) {
+            return;
+        }
+        self.entries.push(line.to_string());
+    }
+
+    // stop browsing, the next history-prev starts from the newest entry again
+    pub fn reset_browsing(&mut self) {
+        self.browsing = None;
+    }
+
+    pub fn older(&mut self, current: &str) -> Option<&str> {
+        let index = match &self.browsing {
+            Some((index, _)) => index.checked_sub(1)?,
+            None => self.entries.len().checked_sub(1)?,
+        };
+        let draft = match self.browsing.take() {
+            Some((_, draft)) => draft,
+            None => current.to_string(),
+        };
+        self.browsing = Some((index, draft));
+        Some(&self.entries[index])
+    }
+
+    // going past the newest entry gives back what was being typed
+    pub fn newer(&mut self) -> Option<String> {
+        let (index, draft) = self.browsing.take()?;
+        if index + 1 >= self.entries.len() {
+            return Some(draft);
+        }
+        self.browsing = Some((index + 1, draft));
+        Some(self.entries[index + 1].clone())
+    }
+
+    // alt+. : the last argument of the previous command. returns (start, end, text) to put in the prompt.
+    // if the last one we handed out is still sitting behind the cursor it gets swapped for the one before it.
+    pub fn last_argument(&mut self, prompt: &str, cursor: usize) -> Option<(usize, usize, String)> {
+        let (start, end, back) = match self.last_argument {
+            Some((start, end, back)) if end == cursor && end <= prompt.len() => {
+                let previous = self.entries.len().checked_sub(back)?;
+                let still_there = last_argument_of(&self.entries[previous])
+                    .is_some_and(|a| prompt.get(start..end) == Some(a));
+                if still_there {
+                    (start, end, back + 1)
+                } else {
+                    (cursor, cursor, 1)
+                }
+            }
+            _ => (cursor, cursor, 1),
+        };
+
+        // skip over commands that don't have anything we can use
+        let mut back = back;
+        let argument = loop {
+            let index = self.entries.len().checked_sub(back)?;
+            if let Some(a) = last_argument_of(&self.entries[index]) {
+                break a.to_string();
+            }
+            back += 1;
+        };
+
+        self.last_argument = Some((start, start + argument.len(), back));
+        Some((start, end, argument))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::History;
+
+    #[test]
+    fn last_argument_cycles_back() {
+        let mut history = History::default();
+        history.push("ls -la ~/code");
+        history.push("cat \"some file\"");
+        history.push("   ");
+
+        let prompt = String::from("echo ");
+        let (start, end, argument) = history.last_argument(&prompt, 5).unwrap();
+        assert_eq!((start, end, argument.as_str()), (5, 5, "\"some file\""));
+
+        let prompt = format!("echo {argument}");
+        let (start, end, argument) = history.last_argument(&prompt, prompt.len()).unwrap();
+        assert_eq!((start, end, argument.as_str()), (5, 16, "~/code"));
+
+        let prompt = format!("echo {argument}");
+        assert!(history.last_argument(&prompt, prompt.len()).is_none());
+    }
+
+    #[test]
+    fn browsing() {
+        let mut history = History::default();
+        history.push("one");
+        history.push("two");
+
+        assert_eq!(history.older("draft"), Some("two"));
+        assert_eq!(history.older("two"), Some("one"));
+        assert_eq!(history.older("one"), None);
+        assert_eq!(history.newer().as_deref(), Some("two"));
+        assert_eq!(history.newer().as_deref(), Some("draft"));
+        assert_eq!(history.newer(), None);
+
+        // editing what came out of history starts it over from the newest
+        assert_eq!(history.older("draft"), Some("two"));
+        assert_eq!(history.older("two"), Some("one"));
+        history.reset_browsing();
+        assert_eq!(history.older("one edited"), Some("two"));
+        assert_eq!(history.newer().as_deref(), Some("one edited"));
+    }
+}
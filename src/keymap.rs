@@ -0,0 +1,497 @@
+use crate::error::EASHError;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{HashMap, HashSet};
+
+// everything a key can be bound to. the names are what goes in [keybindings]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    AcceptLine,
+    InsertNewline,
+    Quit,
+    DeleteCharOrQuit,
+    Complete,
+    Undo,
+    Redo,
+    SelectAll,
+    BeginningOfLine,
+    EndOfLine,
+    BackwardChar,
+    ForwardChar,
+    SelectBackwardChar,
+    SelectForwardChar,
+    BackwardWord,
+    ForwardWord,
+    BackwardSegment,
+    ForwardSegment,
+    SelectBackwardSegment,
+    SelectForwardSegment,
+    BackwardDeleteChar,
+    DeleteChar,
+    DeleteWordBackward,
+    TransposeChars,
+    UpcaseWord,
+    DowncaseWord,
+    CapitalizeWord,
+    ClearScreen,
+    YankLastArg,
+    KillLine,
+    BackwardKillLine,
+    KillWord,
+    UnixWordRubout,
+    CopyRegion,
+    Yank,
+    YankPop,
+    HistoryPrev,
+    HistoryNext,
+    UpLineOrHistory,
+    DownLineOrHistory,
+    EditCommandLine,
+}
+
+const ACTIONS: &[(&str, Action)] = &[
+    // (or a newline, if the command isn't finished)
+    ("accept-line", Action::AcceptLine),
+    ("insert-newline", Action::InsertNewline),
+    ("quit", Action::Quit),
+    ("delete-char-or-quit", Action::DeleteCharOrQuit),
+    ("complete", Action::Complete),
+    ("undo", Action::Undo),
+    ("redo", Action::Redo),
+    ("select-all", Action::SelectAll),
+    ("beginning-of-line", Action::BeginningOfLine),
+    ("end-of-line", Action::EndOfLine),
+    ("backward-char", Action::BackwardChar),
+    ("forward-char", Action::ForwardChar),
+    ("select-backward-char", Action::SelectBackwardChar),
+    ("select-forward-char", Action::SelectForwardChar),
+    ("backward-word", Action::BackwardWord),
+    ("forward-word", Action::ForwardWord),
+    // segments stop at / . , quotes and spaces, handy for paths
+    ("backward-segment", Action::BackwardSegment),
+    ("forward-segment", Action::ForwardSegment),
+    ("select-backward-segment", Action::SelectBackwardSegment),
+    ("select-forward-segment", Action::SelectForwardSegment),
+    ("backward-delete-char", Action::BackwardDeleteChar),
+    ("delete-char", Action::DeleteChar),
+    ("delete-word-backward", Action::DeleteWordBackward),
+    ("transpose-chars", Action::TransposeChars),
+    ("upcase-word", Action::UpcaseWord),
+    ("downcase-word", Action::DowncaseWord),
+    ("capitalize-word", Action::CapitalizeWord),
+    ("clear-screen", Action::ClearScreen),
+    ("yank-last-arg", Action::YankLastArg),
+    ("kill-line", Action::KillLine),
+    ("backward-kill-line", Action::BackwardKillLine),
+    ("kill-word", Action::KillWord),
+    ("unix-word-rubout", Action::UnixWordRubout),
+    ("copy-region", Action::CopyRegion),
+    ("yank", Action::Yank),
+    ("yank-pop", Action::YankPop),
+    ("history-prev", Action::HistoryPrev),
+    ("history-next", Action::HistoryNext),
+    ("up-line-or-history", Action::UpLineOrHistory),
+    ("down-line-or-history", Action::DownLineOrHistory),
+    // open the prompt in $VISUAL/$EDITOR
+    ("edit-command-line", Action::EditCommandLine),
+];
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS.iter().find(|(n, _)| *n == name).map(|(_, a)| *a)
+    }
+
+    pub fn name(&self) -> &'static str {
+        ACTIONS
+            .iter()
+            .find(|(_, a)| a == self)
+            .map(|(n, _)| *n)
+            .unwrap()
+    }
+}
+
+pub const DEFAULT_KEYBINDINGS: &[(&str, &str)] = &[
+    ("enter", "accept-line"),
+    ("alt-enter", "insert-newline"),
+    ("ctrl-c", "quit"),
+    ("ctrl-d", "delete-char-or-quit"),
+    ("tab", "complete"),
+    // ctrl+_ comes through as ctrl+7 on most terminals (it's 0x1F)
+    ("ctrl-_", "undo"),
+    ("ctrl-7", "undo"),
+    ("ctrl-z", "undo"),
+    ("alt-_", "redo"),
+    // (select-all beats bash's beginning-of-line here, home still does that)
+    ("ctrl-a", "select-all"),
+    ("home", "beginning-of-line"),
+    ("ctrl-e", "end-of-line"),
+    ("end", "end-of-line"),
+    ("ctrl-b", "backward-char"),
+    ("left", "backward-char"),
+    ("ctrl-f", "forward-char"),
+    ("right", "forward-char"),
+    ("shift-left", "select-backward-char"),
+    ("shift-right", "select-forward-char"),
+    ("alt-b", "backward-word"),
+    ("alt-f", "forward-word"),
+    ("ctrl-left", "backward-segment"),
+    ("ctrl-right", "forward-segment"),
+    ("ctrl-shift-left", "select-backward-segment"),
+    ("ctrl-shift-right", "select-forward-segment"),
+    ("backspace", "backward-delete-char"),
+    ("delete", "delete-char"),
+    // for some reason some terminals send ctrl+h for ctrl+backspace
+    ("ctrl-h", "delete-word-backward"),
+    ("ctrl-backspace", "delete-word-backward"),
+    ("ctrl-t", "transpose-chars"),
+    ("alt-u", "upcase-word"),
+    ("alt-l", "downcase-word"),
+    ("alt-c", "capitalize-word"),
+    ("ctrl-l", "clear-screen"),
+    ("alt-.", "yank-last-arg"),
+    ("ctrl-k", "kill-line"),
+    ("ctrl-u", "backward-kill-line"),
+    ("alt-d", "kill-word"),
+    ("ctrl-w", "unix-word-rubout"),
+    ("alt-w", "copy-region"),
+    ("ctrl-y", "yank"),
+    ("alt-y", "yank-pop"),
+    ("up", "up-line-or-history"),
+    ("ctrl-p", "up-line-or-history"),
+    ("down", "down-line-or-history"),
+    ("ctrl-n", "down-line-or-history"),
+    ("ctrl-x ctrl-e", "edit-command-line"),
+];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Chord {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+}
+
+impl Chord {
+    pub fn new(code: KeyCode, modifiers: KeyModifiers) -> Chord {
+        let mut modifiers =
+            modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT);
+        // the character already says whether shift was held, and terminals don't agree on sending it
+        let mut code = code;
+        if let KeyCode::Char(c) = code {
+            modifiers.remove(KeyModifiers::SHIFT);
+            // and ctrl+A is just ctrl+a to a terminal
+            if modifiers.contains(KeyModifiers::CONTROL) {
+                code = KeyCode::Char(c.to_ascii_lowercase());
+            }
+        }
+        Chord { code, modifiers }
+    }
+
+    // "ctrl-x", "alt-.", "ctrl-shift-left", "f5"...
+    pub fn parse(s: &str) -> Result<Chord, EASHError> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = s;
+        loop {
+            let lower = rest.to_lowercase();
+            let (modifier, length) = if lower.starts_with("ctrl-") {
+                (KeyModifiers::CONTROL, 5)
+            } else if lower.starts_with("alt-") {
+                (KeyModifiers::ALT, 4)
+            } else if lower.starts_with("shift-") {
+                (KeyModifiers::SHIFT, 6)
+            } else {
+                break;
+            };
+            modifiers |= modifier;
+            rest = &rest[length..];
+        }
+
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match rest.to_lowercase().as_str() {
+                "enter" | "return" => KeyCode::Enter,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" => KeyCode::Insert,
+                "esc" | "escape" => KeyCode::Esc,
+                "space" => KeyCode::Char(' '),
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                f if f.starts_with('f')
+                    && let Ok(n) = f[1..].parse::<u8>() =>
+                {
+                    KeyCode::F(n)
+                }
+                _ => return Err(EASHError::ConfigInvalidKey(s.to_string())),
+            },
+        };
+
+        Ok(Chord::new(code, modifiers))
+    }
+}
+
+// chords separated by spaces, "ctrl-x ctrl-e"
+fn parse_sequence(s: &str) -> Result<Vec<Chord>, EASHError> {
+    let sequence = s
+        .split_whitespace()
+        .map(Chord::parse)
+        .collect::<Result<Vec<_>, _>>()?;
+    if sequence.is_empty() {
+        return Err(EASHError::ConfigInvalidKey(s.to_string()));
+    }
+    Ok(sequence)
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyResult {
+    Action(Action),
+    // in the middle of a sequence, wait for the next key
+    Pending,
+    Insert(char),
+    // nothing bound (or a sequence that went nowhere)
+    Unbound,
+}
+
+#[derive(Default)]
+pub struct Keymap {
+    bindings: HashMap<Vec<Chord>, Action>,
+    prefixes: HashSet<Vec<Chord>>,
+    pending: Vec<Chord>,
+    // bindings that got thrown out because another one made them unreachable, for telling the user
+    pub conflicts: Vec<String>,
+}
+
+impl Keymap {
+    // the default keymap with [keybindings] from the config on top.
+    // binding something to "none" gets rid of it
+    pub fn new(config: &HashMap<String, String>) -> Result<Keymap, EASHError> {
+        let mut keymap = Keymap::default();
+        // (sequence, what it was called in the config, action, whether the user put it there)
+        let mut bindings: Vec<(Vec<Chord>, String, Option<Action>, bool)> = Vec::new();
+        for (keys, action) in DEFAULT_KEYBINDINGS {
+            bindings.push((
+                parse_sequence(keys)?,
+                keys.to_string(),
+                Action::from_name(action),
+                false,
+            ));
+        }
+
+        // sorted so what wins a conflict doesn't depend on hashmap order
+        let mut user: Vec<_> = config.iter().collect();
+        user.sort();
+        for (keys, action) in user {
+            let sequence = parse_sequence(keys)?;
+            let action = match action.as_str() {
+                "none" => None,
+                name => Some(
+                    Action::from_name(name)
+                        .ok_or_else(|| EASHError::ConfigInvalidAction(name.to_string()))?,
+                ),
+            };
+
+            if let Some(i) = bindings.iter().position(|(s, ..)| *s == sequence) {
+                if bindings[i].3 {
+                    keymap.conflicts.push(format!(
+                        "`{keys}` and `{}` are the same key, ignoring `{keys}`",
+                        bindings[i].1
+                    ));
+                    continue;
+                }
+                bindings.remove(i);
+            }
+            bindings.push((sequence, keys.clone(), action, true));
+        }
+        bindings.retain(|(.., action, _)| action.is_some());
+
+        // a binding that starts with another whole binding can never be reached.
+        // yours beat the defaults, and otherwise the shorter one stays
+        let mut dropped = HashSet::new();
+        for (i, (sequence, keys, action, by_user)) in bindings.iter().enumerate() {
+            for (j, (other, other_keys, other_action, other_by_user)) in bindings.iter().enumerate()
+            {
+                if i == j || other.len() >= sequence.len() || !sequence.starts_with(other) {
+                    continue;
+                }
+                let (loser, winner) = if *by_user && !other_by_user {
+                    (j, (keys, action))
+                } else {
+                    (i, (other_keys, other_action))
+                };
+                // (something shadowed by more than one binding only needs mentioning once)
+                if !dropped.insert(loser) {
+                    continue;
+                }
+                let (loser_keys, loser_action) = (&bindings[loser].1, &bindings[loser].2);
+                keymap.conflicts.push(format!(
+                    "`{loser_keys}` ({}) is shadowed by `{}` ({}), ignoring it",
+                    loser_action.unwrap().name(),
+                    winner.0,
+                    winner.1.unwrap().name()
+                ));
+            }
+        }
+
+        for (i, (sequence, _, action, _)) in bindings.into_iter().enumerate() {
+            if dropped.contains(&i) {
+                continue;
+            }
+            for length in 1..sequence.len() {
+                keymap.prefixes.insert(sequence[..length].to_vec());
+            }
+            keymap.bindings.insert(sequence, action.unwrap());
+        }
+
+        Ok(keymap)
+    }
+
+    pub fn feed(&mut self, event: &KeyEvent) -> KeyResult {
+        let chord = Chord::new(event.code, event.modifiers);
+        self.pending.push(chord);
+
+        if let Some(action) = self.bindings.get(&self.pending) {
+            self.pending.clear();
+            return KeyResult::Action(*action);
+        }
+        if self.prefixes.contains(&self.pending) {
+            return KeyResult::Pending;
+        }
+
+        let was_sequence = self.pending.len() > 1;
+        self.pending.clear();
+        match chord.code {
+            KeyCode::Char(c)
+                if !was_sequence
+                    && !chord
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                KeyResult::Insert(c)
+            }
+            _ => KeyResult::Unbound,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keymap::{Action, Chord, KeyResult, Keymap};
+    use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+    use std::collections::HashMap;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parse_chords() {
+        let chord = Chord::parse("Ctrl-Shift-Left").unwrap();
+        assert_eq!(chord.code, KeyCode::Left);
+        assert_eq!(chord.modifiers, KeyModifiers::CONTROL | KeyModifiers::SHIFT);
+
+        assert_eq!(Chord::parse("alt--").unwrap().code, KeyCode::Char('-'));
+        assert_eq!(Chord::parse("f12").unwrap().code, KeyCode::F(12));
+        assert_eq!(
+            Chord::parse("A").unwrap(),
+            Chord::new(KeyCode::Char('A'), KeyModifiers::SHIFT)
+        );
+        assert!(Chord::parse("ctrl-nonsense").is_err());
+    }
+
+    #[test]
+    fn sequences_and_inserting() {
+        let config = HashMap::from([("ctrl-x ctrl-k".to_string(), "kill-line".to_string())]);
+        let mut keymap = Keymap::new(&config).unwrap();
+        assert!(keymap.conflicts.is_empty());
+
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('x'), ctrl)),
+            KeyResult::Pending
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('k'), ctrl)),
+            KeyResult::Action(Action::KillLine)
+        );
+
+        // a sequence that goes nowhere gets eaten whole
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('x'), ctrl)),
+            KeyResult::Pending
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('q'), KeyModifiers::NONE)),
+            KeyResult::Unbound
+        );
+
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('Q'), KeyModifiers::SHIFT)),
+            KeyResult::Insert('Q')
+        );
+        // ctrl+_ is 0x1F, which crossterm reports as ctrl+7
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('7'), ctrl)),
+            KeyResult::Action(Action::Undo)
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('h'), ctrl)),
+            KeyResult::Action(Action::DeleteWordBackward)
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('z'), ctrl)),
+            KeyResult::Action(Action::Undo)
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('a'), ctrl)),
+            KeyResult::Action(Action::SelectAll)
+        );
+    }
+
+    #[test]
+    fn conflicts() {
+        let config = HashMap::from([
+            ("ctrl-a ctrl-a".to_string(), "select-all".to_string()),
+            ("Ctrl-E".to_string(), "quit".to_string()),
+            ("ctrl-e".to_string(), "undo".to_string()),
+            ("ctrl-t".to_string(), "none".to_string()),
+        ]);
+        let mut keymap = Keymap::new(&config).unwrap();
+        assert_eq!(keymap.conflicts.len(), 2);
+
+        // yours beats the default ctrl-a
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('a'), ctrl)),
+            KeyResult::Pending
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('a'), ctrl)),
+            KeyResult::Action(Action::SelectAll)
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('e'), ctrl)),
+            KeyResult::Action(Action::Quit)
+        );
+        assert_eq!(
+            keymap.feed(&key(KeyCode::Char('t'), ctrl)),
+            KeyResult::Unbound
+        );
+
+        // alt-g alt-g alt-g is under both alt-g and alt-g alt-g, but only gets reported once
+        let config = HashMap::from([
+            ("alt-g".to_string(), "quit".to_string()),
+            ("alt-g alt-g".to_string(), "redo".to_string()),
+            ("alt-g alt-g alt-g".to_string(), "undo".to_string()),
+        ]);
+        assert_eq!(Keymap::new(&config).unwrap().conflicts.len(), 2);
+
+        let config = HashMap::from([("ctrl-a".to_string(), "teleport".to_string())]);
+        assert!(Keymap::new(&config).is_err());
+    }
+}
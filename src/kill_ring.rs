@@ -0,0 +1,86 @@
+use crate::misc_types::Direction;
+use std::collections::VecDeque;
+
+// emacs style kill ring. the front is whatever was killed most recently.
+pub struct KillRing {
+    entries: VecDeque<String>,
+    capacity: usize,
+    // what the prompt looked like right after the last kill, if it still looks like this the next kill
+    // gets glued onto the same entry
+    after_kill: Option<(String, usize)>,
+    // (start, end, ring index) of the last yank, so alt-y knows what to swap out
+    last_yank: Option<(usize, usize, usize)>,
+}
+
+impl Default for KillRing {
+    fn default() -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: 32, // TODO)) make configurable
+            after_kill: None,
+            last_yank: None,
+        }
+    }
+}
+
+impl KillRing {
+    // whether a kill right now would get glued onto the last one (nothing happened in between)
+    pub fn continues(&self, prompt: &str, cursor: usize) -> bool {
+        self.after_kill
+            .as_ref()
+            .is_some_and(|(p, c)| p == prompt && *c == cursor)
+    }
+
+    // add killed text to the ring. prompt & cursor are what they are AFTER the kill.
+    pub fn kill(
+        &mut self,
+        text: String,
+        direction: Direction,
+        continues: bool,
+        prompt: &str,
+        cursor: usize,
+    ) {
+        if text.is_empty() {
+            return;
+        }
+
+        match self.entries.front_mut() {
+            Some(front) if continues => match direction {
+                Direction::Left => front.insert_str(0, &text),
+                Direction::Right => front.push_str(&text),
+            },
+            _ => {
+                self.entries.push_front(text);
+                self.entries.truncate(self.capacity);
+            }
+        }
+
+        self.after_kill = Some((prompt.to_string(), cursor));
+        self.last_yank = None;
+    }
+
+    // the thing to yank at position `at`
+    pub fn yank(&mut self, at: usize) -> Option<&str> {
+        let text = self.entries.front()?;
+        self.last_yank = Some((at, at + text.len(), 0));
+        self.after_kill = None;
+        Some(text)
+    }
+
+    // swap the last yank for the next oldest entry. only works if the last yanked text is still sitting
+    // right behind the cursor. returns (start, end, replacement).
+    pub fn rotate(&mut self, prompt: &str, cursor: usize) -> Option<(usize, usize, &str)> {
+        let (start, end, index) = self.last_yank?;
+        let still_there =
+            cursor == end && prompt.get(start..end) == self.entries.get(index).map(|e| e.as_str());
+        if !still_there {
+            self.last_yank = None;
+            return None;
+        }
+
+        let next = (index + 1) % self.entries.len();
+        let text = self.entries.get(next)?;
+        self.last_yank = Some((start, start + text.len(), next));
+        Some((start, end, text))
+    }
+}
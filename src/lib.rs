@@ -1,10 +1,29 @@
+pub mod abbreviations;
+pub mod actions;
 pub mod chain;
+pub mod clipboard;
+pub mod color_depth;
+pub mod completion;
 pub mod config;
 pub mod draw;
+pub mod editor;
 pub mod element;
 pub mod error;
 pub mod evaluate;
+pub mod gradient;
+#[cfg(test)]
+pub mod harness;
+pub mod history;
+pub mod keymap;
+pub mod kill_ring;
 pub mod misc_types;
+pub mod mouse;
+pub mod pairs;
 pub mod prompt;
+pub mod renderer;
 pub mod run;
-pub mod state;
\ No newline at end of file
+pub mod scheduler;
+pub mod state;
+pub mod surface;
+pub mod undo;
+pub mod vi;
@@ -1,16 +1,37 @@
 use eash::{
-    chain::{Chain, ChainLink, ChainMass, step_links},
+    abbreviations::Abbreviations,
+    actions::{Edited, edit},
+    chain::{Chain, SideChain, is_settled, step_links},
+    clipboard::set_clipboard,
+    color_depth::{ColorChoice, ColorDepth, detect},
+    completion::Completer,
     config::{GlyphList, file_to_config, find_config, get_elements_from_config},
     draw::draw,
-    element::ElementType,
+    editor::edit_externally,
     error::EASHError,
-    misc_types::Direction,
-    prompt::Prompt,
+    evaluate::is_incomplete,
+    history::History,
+    keymap::{Action, KeyResult, Keymap},
+    kill_ring::KillRing,
+    misc_types::{Direction, EASHPallete},
+    mouse::MouseState,
+    prompt::{Prompt, display_width},
+    renderer::Renderer,
+    scheduler::{Scheduler, earliest},
+    surface::GridSurface,
+    undo::EditKind,
+    vi::{ViConfig, ViMode, ViResult, ViState},
 };
 
 use crossterm::{
-    event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
-    terminal::{disable_raw_mode, enable_raw_mode},
+    cursor::{MoveDown, MoveTo, SetCursorStyle, position},
+    event::{
+        self, DisableBracketedPaste, DisableMouseCapture, EnableBracketedPaste, EnableMouseCapture,
+        Event,
+    },
+    execute,
+    style::Print,
+    terminal::{self, Clear, ClearType, disable_raw_mode, enable_raw_mode},
 };
 
 use std::{
@@ -22,56 +43,116 @@ use std::{
     time::{Duration, Instant},
 };
 
+// put the terminal back how we found it
+fn restore_terminal() {
+    let _ = disable_raw_mode();
+    let _ = execute!(
+        std::io::stdout(),
+        DisableBracketedPaste,
+        DisableMouseCapture,
+        SetCursorStyle::DefaultUserShape
+    );
+}
+
+fn setup_terminal(mouse: bool) {
+    enable_raw_mode().expect("Oh mah gawd.");
+    // pastes come in as one big event instead of a pile of keypresses
+    _ = execute!(std::io::stdout(), EnableBracketedPaste);
+    if mouse {
+        _ = execute!(std::io::stdout(), EnableMouseCapture);
+    }
+}
+
 fn init_panic_hook() {
     let original_hook = take_hook();
     set_hook(Box::new(move |info| {
-        let _ = disable_raw_mode();
+        restore_terminal();
         original_hook(info);
     }));
 }
 
-// TODO)) right now this just kind turns the result into an option... probably don't need this.
-fn read_ct_keypress_event(event_result: std::io::Result<Event>) -> Option<KeyEvent> {
-    // if we recieved an error just return.
-    if event_result.is_err() {
-        // Golang lookin ass error handling
-        println!(
-            "Got error while reading input: {}",
-            event_result.unwrap_err()
-        );
-        return None;
-    }
-
-    event_result.unwrap().as_key_event()
-}
+const FRAME_TIME: Duration = Duration::from_millis(1000 / 60); // TODO)) make configurable
+// after sitting still for a while the springs shouldn't try to catch up all at once
+const MAX_STEP: Duration = Duration::from_millis(1000 / 30);
 
 fn init_draw_thread<W: Write + Send + 'static>(
     element_mutex: Arc<Mutex<Chain>>,
+    scheduler: Scheduler,
     glyphs: GlyphList,
+    pallete: EASHPallete,
+    vi: ViConfig,
+    depth: ColorDepth,
     w: W,
 ) {
-    let mut w = w;
+    let mut renderer = Renderer::new(w, depth);
     thread::Builder::new()
         .name("Rendering".to_string())
         .spawn(move || {
-            let mut instant = Instant::now();
+            // the springs always move in FRAME_TIME sized steps, however often frames get drawn, so a
+            // keypress in between frames doesn't give them an odd sized one. this is how far behind they are
+            let mut last_step = Instant::now();
+            let mut behind = Duration::ZERO;
+            let mut next_frame = None;
             loop {
-                thread::sleep(Duration::from_millis(1000 / 60)); // TODO)) make configurable
-                let lock_result = element_mutex.try_lock();
-                if lock_result.is_err() {
-                    continue;
-                };
+                // nothing to do until something happens or something's due
+                scheduler.wait(next_frame);
+                let frame_start = Instant::now();
 
-                let mut lock = lock_result.unwrap();
-                step_links(&mut lock, instant.elapsed().as_nanos() as f32 * 1e-9);
-                draw(&mut w, &mut lock, &glyphs).expect("render esploded 💥💥💥");
-                instant = Instant::now();
+                let mut lock = element_mutex.lock().unwrap();
+                let width = terminal::size().map_or(80, |(columns, _)| columns);
+                lock.columns = width;
+                behind = (behind + frame_start.duration_since(last_step)).min(MAX_STEP);
+                last_step = frame_start;
+                // (stepping a settled chain just makes it jiggle)
+                if is_settled(&lock) {
+                    behind = Duration::ZERO;
+                }
+                while behind >= FRAME_TIME {
+                    step_links(&mut lock, FRAME_TIME.as_secs_f32());
+                    behind -= FRAME_TIME;
+                }
+                if lock.repaint {
+                    // the terminal's cursor is wherever main left it
+                    renderer.repaint(lock.cursor_row);
+                    lock.repaint = false;
+                }
+                renderer.top = lock.top_row;
+                renderer.height = terminal::size().map_or(u16::MAX, |(_, rows)| rows);
+                let mut frame = GridSurface::new(width);
+                let next_change = draw(&mut frame, &mut lock, &glyphs, &pallete, &vi)
+                    .expect("render esploded 💥💥💥");
+                renderer.present(frame).expect("render esploded 💥💥💥");
+                lock.top_row = renderer.top;
+
+                // (whenever the next step is due)
+                let moving = (!is_settled(&lock)).then_some(frame_start + FRAME_TIME - behind);
+                drop(lock);
+                scheduler.frame_done();
+                // (but never faster than FRAME_TIME unless someone asks)
+                next_frame = earliest(next_change, moving).map(|n| n.max(frame_start + FRAME_TIME));
             }
         })
         .expect("erm.... what the thread?");
 }
 
+// --color=never|auto|always (or --color never). nothing else to parse yet
+fn parse_args() -> Result<ColorChoice, EASHError> {
+    let mut choice = ColorChoice::Auto;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if let Some(value) = arg.strip_prefix("--color=") {
+            choice = ColorChoice::parse(value)?;
+        } else if arg == "--color" {
+            choice = ColorChoice::parse(&args.next().unwrap_or_default())?;
+        } else {
+            return Err(EASHError::InvalidArgument(arg));
+        }
+    }
+    Ok(choice)
+}
+
 fn main() -> Result<(), EASHError> {
+    let color_choice = parse_args()?;
     let config_path = find_config()?;
     if config_path.is_none() {
         // WE'RE JUST GONNA KILL EM!!!!
@@ -81,129 +162,414 @@ fn main() -> Result<(), EASHError> {
         return Ok(());
     }
 
+    // i feel like arc<mutex<T>>'s are sheltering me from a cruel and inhuman data management fact
+    let prompt = Arc::new(Mutex::new(Prompt::default()));
+
     let elements;
     let spring;
+    let right;
+    let above;
     let glyphs;
+    let mut completer;
+    let pallete: EASHPallete;
+    let vi_config: ViConfig;
+    let mut keymap;
+    let auto_pair;
+    let paste_bump;
+    let mouse_enabled;
+    let execute_after_edit;
+    let mut abbreviations;
     {
         // TODO)) proper handling for this
         let config_struct = file_to_config(config_path.unwrap())?;
-        elements = get_elements_from_config(&config_struct)?;
+        elements = get_elements_from_config(&config_struct.chain_elements, &prompt)?;
         spring = config_struct.spring;
+        // the other chains get the main one's springs unless they say otherwise
+        right = SideChain::new(
+            config_struct.right_spring.unwrap_or(spring.clone()).into(),
+            get_elements_from_config(&config_struct.right_elements, &prompt)?,
+        );
+        above = SideChain::new(
+            config_struct.above_spring.unwrap_or(spring.clone()).into(),
+            get_elements_from_config(&config_struct.above_elements, &prompt)?,
+        );
         glyphs = config_struct.glyphs;
+        completer = Completer::new(config_struct.completions);
+        pallete = config_struct.colors.try_into()?;
+        vi_config = config_struct.vi;
+        keymap = Keymap::new(&config_struct.keybindings)?;
+        auto_pair = config_struct.editing.auto_pair;
+        paste_bump = config_struct.editing.paste_bump;
+        mouse_enabled = config_struct.editing.mouse;
+        execute_after_edit = config_struct.editing.execute_after_edit;
+        abbreviations = Abbreviations::new(config_struct.abbreviations);
+    }
+
+    // not worth refusing to start over, but you should know
+    for conflict in &keymap.conflicts {
+        eprintln!("keybinding conflict: {conflict}");
     }
 
     // just disables raw mode when we panic
     init_panic_hook();
 
-    // i feel like arc<mutex<T>>'s are sheltering me from a cruel and inhuman data management fact
-    let prompt = Arc::new(Mutex::new(Prompt {
-        cursor_position: 0,
-        prompt: "".to_string(),
-        selection_start: None,
-    }));
+    {
+        let mut lock = prompt.lock().unwrap();
+        lock.auto_pair = auto_pair;
+        if vi_config.enabled {
+            lock.vi_mode = Some(ViMode::Insert);
+        }
+    }
 
-    // TODO)) move chain propagation into its own function
-    let mut l: Vec<ChainLink> = elements
-        .into_iter()
-        .enumerate()
-        .map(|(i, e)| ChainLink {
-            mass: ChainMass {
-                // TODO)) chainmass should set itself intelligently 🧠🧠🧠 instead of being defined here...
-                position: i as f32 - 10.0,
-                mass: 1.0,
-                velocity: 0.0,
-                width: 0, // set by render function...
-            },
-            element: e,
-        })
-        .collect();
-    l.push(ChainLink {
-        // prompt gets special treatment because 💤
-        mass: ChainMass {
-            position: l.len() as f32 - 10.0,
-            mass: 1.0,
-            velocity: 0.0,
-            width: 0,
-        },
-        element: ElementType::Prompt(prompt.clone()),
-    });
-
-    let chain = Arc::new(Mutex::new(Chain {
-        spring: spring.into(),
-        links: l,
-    }));
+    let mut chain = Chain::new(spring.into(), elements, prompt.clone());
+    chain.right = right;
+    chain.above = above;
 
-    enable_raw_mode().expect("Oh mah gawd.");
+    setup_terminal(mouse_enabled);
+    // the only time we ask the terminal where we are, after this everything that moves the chain
+    // keeps top_row up to date itself
+    chain.top_row = position().map_or(0, |(_, row)| row);
+    let chain = Arc::new(Mutex::new(chain));
     // TODO)) OH MY FUCKING GOD WE CLONING WHOLE ASS HASHMAPS NOW!??! BROTHA NOOO!!!!
-    init_draw_thread(chain.clone(), glyphs, std::io::stdout());
+    let scheduler = Scheduler::default();
+    init_draw_thread(
+        chain.clone(),
+        scheduler.clone(),
+        glyphs,
+        pallete,
+        vi_config,
+        detect(color_choice),
+        std::io::stdout(),
+    );
 
     fn bump(chain: &Arc<Mutex<Chain>>, velocity: f32, direction: Direction) {
-        let mut lock = chain.lock().unwrap();
-        // TODO)) make it so we don't have to iterate through the entire chain each time we bump
-        for v in lock.links.iter_mut() {
-            if let ElementType::Prompt(_) = v.element {
-                v.mass.velocity += match direction {
-                    Direction::Left => -velocity,
-                    Direction::Right => velocity,
-                };
-            }
+        chain.lock().unwrap().bump(velocity, direction);
+    }
+
+    // bump it harder if we hit a wall (nothing happened)
+    fn bump_for(chain: &Arc<Mutex<Chain>>, hit_wall: bool, direction: Direction) {
+        let velocity = if hit_wall { 30.0 } else { 10.0 };
+        bump(chain, velocity, direction);
+    }
+
+    fn quit() -> ! {
+        restore_terminal();
+        exit(0);
+    }
+
+    fn copy_to_clipboard(chain: &Arc<Mutex<Chain>>, text: &str) {
+        // hold onto the chain so the render thread can't write in the middle of the escape sequence
+        let _lock = chain.lock().unwrap();
+        _ = set_clipboard(&mut std::io::stdout(), text);
+    }
+
+    // cut a range out of the prompt & onto the kill ring, returns whether to "bump" or not
+    fn kill(
+        prompt: &mut Prompt,
+        kill_ring: &mut KillRing,
+        (start, end): (usize, usize),
+        direction: Direction,
+    ) -> bool {
+        let continues = kill_ring.continues(prompt.text(), prompt.cursor_position);
+        let killed = prompt.kill_range(start, end);
+        if killed.is_empty() {
+            return true;
         }
+        kill_ring.kill(
+            killed,
+            direction,
+            continues,
+            prompt.text(),
+            prompt.cursor_position,
+        );
+        false
     }
 
+    let mut kill_ring = KillRing::default();
+    let mut vi = ViState::default();
+    let mut history = History::default();
+    // the prompt's revision right after history last filled it in. if it's moved on since, the line got
+    // edited & going up starts from the newest entry again
+    let mut browsed = None;
+    let mut mouse = MouseState::default();
+    // an action to run without waiting for a key first (like accepting the line after editing it in $EDITOR)
+    let mut queued_action = None;
+
     loop {
-        let keypress_event = read_ct_keypress_event(event::read());
-        if keypress_event.is_none() {
-            continue;
+        // whatever happened last time around needs drawing
+        scheduler.request_redraw();
+        let event = match queued_action {
+            Some(_) => None,
+            None => match event::read() {
+                Ok(event) => Some(event),
+                Err(e) => {
+                    // Golang lookin ass error handling
+                    println!("Got error while reading input: {e}");
+                    continue;
+                }
+            },
         };
-        let keypress_event = keypress_event.unwrap();
 
         let mut lock = prompt.lock().unwrap();
-        match keypress_event.code {
-            KeyCode::Char(c) => {
-                if c == 'c' && keypress_event.modifiers.contains(KeyModifiers::CONTROL) {
-                    disable_raw_mode().expect("Oh mah gawd.");
-                    exit(0);
-                }
-
-                if (c == 'w' || c == 'h' || c == '7') // for some reason different terminals output these for ctrl backspace
-                    && keypress_event.modifiers.contains(KeyModifiers::CONTROL)
-                {
-                    if lock.ctrl_backspace() {
-                        bump(&chain, 50.0, Direction::Left);
+
+        let action = match (queued_action.take(), event) {
+            (Some(action), _) => action,
+            (_, Some(Event::Key(keypress_event))) => {
+                // vi gets first dibs on keys when it's turned on
+                match vi.handle_key(&mut lock, &mut kill_ring, &keypress_event) {
+                    ViResult::Handled => continue,
+                    ViResult::Bump(direction) => {
+                        bump(&chain, 30.0, direction);
+                        continue;
                     }
+                    ViResult::PassThrough => {}
+                }
+
+                match keymap.feed(&keypress_event) {
+                    KeyResult::Action(action) => action,
+                    KeyResult::Insert(c) => {
+                        if abbreviations.type_character(&mut lock, c) {
+                            bump(&chain, 20.0, Direction::Right);
+                        }
+                        continue;
+                    }
+                    KeyResult::Pending | KeyResult::Unbound => continue,
+                }
+            }
+            (_, Some(Event::Paste(text))) => {
+                // one undo step & one thud, and newlines stay in the buffer instead of running anything
+                let text = text.replace("\r\n", "\n").replace('\r', "\n");
+                lock.insert_str(&text, EditKind::Paste);
+                bump(&chain, paste_bump, Direction::Right);
+                continue;
+            }
+            // clicking on an element can run an action too
+            (_, Some(Event::Mouse(mouse_event))) => {
+                match mouse.handle(&mouse_event, &mut lock, &chain) {
+                    Some(action) => action,
+                    None => continue,
+                }
+            }
+            (_, Some(Event::Resize(columns, rows))) => {
+                // the terminal's rewrapped whatever was on screen, so what we drew last is gone.
+                // the chain settles into the new width on its own
+                let mut chain_lock = chain.lock().unwrap();
+                chain_lock.columns = columns;
+                let highest = rows.saturating_sub(chain_lock.rows);
+                chain_lock.top_row = chain_lock.top_row.min(highest);
+                chain_lock.repaint = true;
+                continue;
+            }
+            _ => continue,
+        };
+
+        let cursor = lock.cursor_position;
+        match edit(&mut lock, action) {
+            Edited::Bump(velocity, direction) => {
+                bump(&chain, velocity, direction);
+                continue;
+            }
+            Edited::Quietly => continue,
+            Edited::NotAnEdit => {}
+        }
+        match action {
+            Action::AcceptLine => {
+                if abbreviations.expand(&mut lock) {
+                    bump(&chain, 20.0, Direction::Right);
+                }
+                if is_incomplete(lock.text()) {
+                    lock.insert_character('\n');
                     continue;
                 }
 
-                lock.insert_character(c);
+                // TODO)) actually run the thing. for now it just goes into history (and builtins run) and
+                // the old line is left on screen like a real shell would
+                let line = lock.text().to_string();
+                history.push(&line);
+                let output = abbreviations.run_builtin(&line).unwrap_or_default();
+
+                // get one last frame out, so what's left on screen is what actually got accepted.
+                // then hold the chain so it can't draw again until we've moved on
+                drop(lock);
+                scheduler.redraw_now();
+                let mut chain_lock = chain.lock().unwrap();
+                lock = prompt.lock().unwrap();
+                lock.reset();
+
+                // get below the whole prompt first, the cursor might be on any of its lines
+                let below = chain_lock.rows - 1 - chain_lock.cursor_row;
+                if below > 0 {
+                    _ = execute!(std::io::stdout(), MoveDown(below));
+                }
+                _ = execute!(std::io::stdout(), Print("\r\n"));
+                let (columns, height) = terminal::size().unwrap_or((80, u16::MAX));
+                let mut printed = 0;
+                for line in output {
+                    _ = execute!(std::io::stdout(), Print(format!("{line}\r\n")));
+                    printed += display_width(&line)
+                        .div_ceil(columns.max(1) as usize)
+                        .max(1) as u16;
+                }
+                // (everything after the bottom row scrolled up instead)
+                let below_old = chain_lock.top_row + chain_lock.rows + printed;
+                chain_lock.top_row = below_old.min(height.saturating_sub(1));
+                chain_lock.rows = 1;
+                chain_lock.cursor_row = 0;
+                chain_lock.repaint = true;
             }
-            KeyCode::Backspace => {
-                // run the backspace function, bump it harder if it returns true
-                if lock.backspace() {
-                    bump(&chain, 30.0, Direction::Left);
-                } else {
-                    bump(&chain, 10.0, Direction::Left);
+            Action::EditCommandLine => {
+                // hold the chain so the renderer stays out of the editor's way
+                let mut chain_lock = chain.lock().unwrap();
+                restore_terminal();
+                let edited = edit_externally(lock.text());
+                setup_terminal(mouse_enabled);
+                // no telling what the editor left on screen
+                chain_lock.repaint = true;
+                drop(chain_lock);
+
+                match edited {
+                    Ok(Some(text)) => {
+                        let end = lock.text().len();
+                        lock.replace_range(0, end, &text, EditKind::Other);
+                        bump(&chain, 20.0, Direction::Right);
+                        if execute_after_edit {
+                            queued_action = Some(Action::AcceptLine);
+                        }
+                    }
+                    // the editor failed or couldn't be found, leave the prompt alone
+                    _ => bump(&chain, 30.0, Direction::Left),
+                }
+            }
+            Action::Quit => quit(),
+            // end of file on an empty line, same as bash (edit() took care of it otherwise)
+            Action::DeleteCharOrQuit => quit(),
+            Action::Complete => {
+                let Some(result) = completer.complete(lock.text(), lock.tokens(), cursor) else {
+                    bump(&chain, 10.0, Direction::Right);
+                    continue;
+                };
+
+                let insert = match result.candidates.as_slice() {
+                    [] => None,
+                    [only] if only.text.ends_with('/') => Some(only.text.clone()),
+                    [only] => Some(format!("{} ", only.text)),
+                    _ => Some(result.common_prefix()),
+                };
+
+                match insert {
+                    Some(text) if text != lock.text()[result.start..result.end] => {
+                        lock.replace_range(result.start, result.end, &text, EditKind::Completion);
+                        bump(&chain, 20.0, Direction::Right);
+                    }
+                    // nothing new to add, so just wobble at them
+                    _ => bump(&chain, 10.0, Direction::Right),
                 }
+                // (after the insert, which would clear them again)
+                lock.show_completions(result.describe());
+            }
+            Action::ClearScreen => {
+                // clear the screen but keep the chain, hold it so nothing draws halfway through
+                let mut chain_lock = chain.lock().unwrap();
+                _ = execute!(std::io::stdout(), Clear(ClearType::All), MoveTo(0, 0));
+                chain_lock.cursor_row = 0;
+                chain_lock.top_row = 0;
+                chain_lock.repaint = true;
             }
-            KeyCode::Left => {
-                let shift = keypress_event.modifiers.contains(KeyModifiers::SHIFT);
-                let ctrl = keypress_event.modifiers.contains(KeyModifiers::CONTROL);
-                // run the arrow function, bump it harder if it returns true
-                if lock.horiziontal_arrow(Direction::Left, shift, ctrl) {
-                    bump(&chain, 30.0, Direction::Left);
+            Action::YankLastArg => match history.last_argument(lock.text(), cursor) {
+                Some((start, end, argument)) => {
+                    lock.replace_range(start, end, &argument, EditKind::Other);
+                    bump(&chain, 10.0, Direction::Right);
+                }
+                None => bump(&chain, 30.0, Direction::Right),
+            },
+            // emacs kills, everything they delete goes onto the kill ring
+            Action::KillLine | Action::BackwardKillLine | Action::KillWord => {
+                let (range, direction) = match action {
+                    // at the end of a line this takes the newline with it, like emacs
+                    Action::KillLine if cursor == lock.line_end() && cursor < lock.text().len() => {
+                        ((cursor, cursor + 1), Direction::Right)
+                    }
+                    Action::KillLine => ((cursor, lock.line_end()), Direction::Right),
+                    Action::BackwardKillLine => ((lock.line_start(), cursor), Direction::Left),
+                    _ => {
+                        let end = lock.word_in_direction(Direction::Right);
+                        ((cursor, end), Direction::Right)
+                    }
+                };
+                let wall = kill(&mut lock, &mut kill_ring, range, direction);
+                bump_for(&chain, wall, direction);
+            }
+            // kills the selection if there is one, otherwise the word behind the cursor
+            Action::UnixWordRubout => {
+                let range = if let Some(selection) = lock.selection_range() {
+                    copy_to_clipboard(&chain, &lock.text()[selection.0..selection.1]);
+                    selection
                 } else {
-                    bump(&chain, 10.0, Direction::Left);
+                    (lock.find_skippable_in_direction(Direction::Left), cursor)
+                };
+                if kill(&mut lock, &mut kill_ring, range, Direction::Left) {
+                    bump(&chain, 50.0, Direction::Left);
                 }
             }
-            KeyCode::Right => {
-                let shift = keypress_event.modifiers.contains(KeyModifiers::SHIFT);
-                let ctrl = keypress_event.modifiers.contains(KeyModifiers::CONTROL);
-                // run the arrow function, bump it harder if it returns true
-                if lock.horiziontal_arrow(Direction::Right, shift, ctrl) {
-                    bump(&chain, 30.0, Direction::Right);
+            Action::CopyRegion => {
+                if let Some((start, end)) = lock.selection_range() {
+                    let text = lock.text()[start..end].to_string();
+                    copy_to_clipboard(&chain, &text);
+                    let continues = kill_ring.continues(lock.text(), lock.cursor_position);
+                    kill_ring.kill(
+                        text,
+                        Direction::Right,
+                        continues,
+                        lock.text(),
+                        lock.cursor_position,
+                    );
+                    lock.selection_start = None;
                 } else {
+                    bump(&chain, 30.0, Direction::Right);
+                }
+            }
+            Action::Yank => match kill_ring.yank(cursor) {
+                Some(text) => {
+                    lock.insert_str(text, EditKind::Paste);
                     bump(&chain, 10.0, Direction::Right);
                 }
+                None => bump(&chain, 30.0, Direction::Right),
+            },
+            Action::YankPop => match kill_ring.rotate(lock.text(), cursor) {
+                Some((start, end, text)) => {
+                    lock.replace_range(start, end, text, EditKind::Other);
+                    bump(&chain, 10.0, Direction::Right);
+                }
+                None => bump(&chain, 30.0, Direction::Right),
+            },
+            Action::HistoryPrev
+            | Action::HistoryNext
+            | Action::UpLineOrHistory
+            | Action::DownLineOrHistory => {
+                let older = matches!(action, Action::HistoryPrev | Action::UpLineOrHistory);
+                if browsed != Some(lock.revision) {
+                    history.reset_browsing();
+                }
+                let line = match older {
+                    true => history.older(lock.text()).map(str::to_string),
+                    false => history.newer(),
+                };
+                let direction = if older {
+                    Direction::Left
+                } else {
+                    Direction::Right
+                };
+                match line {
+                    Some(line) => {
+                        let end = lock.text().len();
+                        lock.replace_range(0, end, &line, EditKind::Other);
+                        browsed = Some(lock.revision);
+                        bump(&chain, 10.0, direction);
+                    }
+                    None => bump(&chain, 30.0, direction),
+                }
             }
+            // the rest only touch the prompt, so edit() already did them
             _ => {}
         }
     }
@@ -1,15 +1,16 @@
-use std::time::Instant;
 use crossterm::style::Color as ctColor;
 use serde::Deserialize;
+use std::time::{Duration, Instant};
 
-use crate::error::EASHError;
+use crate::{error::EASHError, gradient::Gradient};
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Direction {
     Left,
     Right,
 }
 
-#[derive(Deserialize, Clone)]
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
 pub struct HexColor {
     pub r: u8,
     pub g: u8,
@@ -19,23 +20,23 @@ pub struct HexColor {
 pub enum Color {
     Transparent,
     Solid(HexColor),
-    Gradient(HexColor, HexColor),
+    // (boxed, most colors aren't gradients)
+    Gradient(Box<Gradient>),
 }
 
 impl Color {
     pub fn is_gradient(&self) -> bool {
-        matches!(self, Self::Gradient(_, _))
+        matches!(self, Self::Gradient(_))
     }
 
     pub fn to_color_for_char(&self, distance: f32) -> ctColor {
         match self {
             Self::Transparent => self.to_flat_color().unwrap(),
             Self::Solid(_) => self.to_flat_color().unwrap(), // should be safe enough 👍
-            Self::Gradient(f, t) => ctColor::Rgb {
-                r: (f.r as f32 + ((t.r as i16 - f.r as i16) as f32 * distance)) as u8,
-                g: (f.g as f32 + ((t.g as i16 - f.g as i16) as f32 * distance)) as u8,
-                b: (f.b as f32 + ((t.b as i16 - f.b as i16) as f32 * distance)) as u8,
-            },
+            Self::Gradient(g) => {
+                let HexColor { r, g, b } = g.sample(distance);
+                ctColor::Rgb { r, g, b }
+            }
         }
     }
 
@@ -44,7 +45,7 @@ impl Color {
     // for me to have.
     pub fn to_flat_color(&self) -> Result<ctColor, EASHError> {
         match self {
-            Self::Gradient(_, _) => Err(EASHError::ColorNotFlat),
+            Self::Gradient(_) => Err(EASHError::ColorNotFlat),
             Self::Solid(c) => Ok(ctColor::Rgb {
                 r: c.r,
                 g: c.g,
@@ -57,17 +58,50 @@ impl Color {
 
 pub struct EASHPallete {
     // prompt stuff...
-    value_fg: Color,
-    string_fg: Color,
-    flag_fg: Color,
-    andthen_fg: Color,
-    pipe_fg: Color,
+    pub value_fg: Color,
+    pub string_fg: Color,
+    pub directory_fg: Color,
+    pub flag_fg: Color,
+    pub andthen_fg: Color,
+    pub pipe_fg: Color,
+    pub selection_fg: Color,
+    pub selection_bg: Color,
+    pub bracket_match_bg: Color,
+    pub bracket_unbalanced_fg: Color,
+    pub abbreviation_bg: Color,
 
     // not sure if we'll need to use ALL of these, mostly just here as an example i guess.
-    warning_glyph: Glyph,
-    error_glyph: Glyph,
-    processing_glyph: Glyph,
-    waiting_glyph: Glyph,
+    pub warning_glyph: Glyph,
+    pub error_glyph: Glyph,
+    pub processing_glyph: Glyph,
+    pub waiting_glyph: Glyph,
+}
+
+impl Default for EASHPallete {
+    fn default() -> Self {
+        // roughly the xterm colors we used to hardcode
+        let rgb = |r, g, b| Color::Solid(HexColor { r, g, b });
+        Self {
+            value_fg: rgb(229, 229, 229),
+            string_fg: rgb(0, 205, 0),
+            directory_fg: rgb(205, 205, 0),
+            flag_fg: rgb(205, 0, 0),
+            andthen_fg: rgb(205, 0, 205),
+            pipe_fg: rgb(0, 205, 205),
+            selection_fg: rgb(255, 255, 255),
+            selection_bg: rgb(70, 80, 140),
+            bracket_match_bg: rgb(80, 80, 80),
+            bracket_unbalanced_fg: rgb(255, 60, 60),
+            abbreviation_bg: rgb(60, 110, 70),
+            warning_glyph: Glyph::Single('!'),
+            error_glyph: Glyph::Single('✗'),
+            processing_glyph: Glyph::Animated {
+                characters: "⠏⠧⠶⠼⠹⠛".to_string(),
+                speed: 0.1,
+            },
+            waiting_glyph: Glyph::Single('…'),
+        }
+    }
 }
 
 #[derive(Deserialize, Clone)]
@@ -109,13 +143,26 @@ impl Glyph {
                     // is there a better way to do this? please?
                     speed_ms = (speed * 1000.0).floor().to_int_unchecked();
                 };
-                
+
                 let index = (time_since_then % (speed_ms * characters.chars().count() as u128)
                     / speed_ms) as usize;
                 characters.chars().nth(index).unwrap_or('!')
             }
         }
     }
+
+    // when get_current_glyph is going to give back something different, None if never
+    pub fn next_change(&self, then: &Instant) -> Option<Instant> {
+        match self {
+            Glyph::Single(_) => None,
+            Glyph::Animated { speed, .. } => {
+                // same rounding as get_current_glyph, or we'd wake up just before it changes
+                let speed_ms = ((speed * 1000.0).floor() as u64).max(1);
+                let frames_so_far = then.elapsed().as_millis() as u64 / speed_ms;
+                Some(*then + Duration::from_millis(speed_ms * (frames_so_far + 1)))
+            }
+        }
+    }
 }
 
 pub enum TriggerType {
@@ -124,12 +171,57 @@ pub enum TriggerType {
     Timed(f32), // seconds
 }
 
+// the little shape on the end of an element. it's drawn in the element's background color, on
+// top of whatever's next to it
+pub enum Cap {
+    None,
+    Powerline,
+    Rounded,
+    Slant,
+    Custom(String),
+}
+
+impl Cap {
+    // (the shapes are from nerd fonts' powerline extras)
+    pub fn glyph(&self, side: Direction) -> &str {
+        let left = side == Direction::Left;
+        match self {
+            Cap::None => "",
+            Cap::Powerline if left => "\u{e0b2}",
+            Cap::Powerline => "\u{e0b0}",
+            Cap::Rounded if left => "\u{e0b6}",
+            Cap::Rounded => "\u{e0b4}",
+            Cap::Slant if left => "\u{e0ba}",
+            Cap::Slant => "\u{e0bc}",
+            Cap::Custom(glyph) => glyph,
+        }
+    }
+}
+
+// what an element does when the chain doesn't fit in the terminal
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Overflow {
+    // nothing, it just goes off the edge
+    Clip,
+    // cut the content short with a …
+    Ellipsis,
+    // get out of the way entirely
+    Hide,
+    // squeeze Width::Units down (anything else just gets cut)
+    Shrink,
+}
+
 pub struct VisualState {
     pub align: Alignment,
     pub width: Width,
     pub padding: u32,
     pub bg_color: Color,
     pub color: Color,
+    pub left_cap: Cap,
+    pub right_cap: Cap,
+    pub overflow: Overflow,
+    // when there isn't room, the lowest priority elements give it up first
+    pub priority: i32,
 }
 
 impl Default for VisualState {
@@ -144,6 +236,10 @@ impl Default for VisualState {
             padding: 1,
             bg_color: Color::Transparent,
             width: Width::Minimum(0),
+            left_cap: Cap::None,
+            right_cap: Cap::None,
+            overflow: Overflow::Clip,
+            priority: 0,
         }
     }
 }
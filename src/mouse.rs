@@ -0,0 +1,119 @@
+use crate::{
+    chain::{Chain, Strand},
+    element::ElementType,
+    keymap::Action,
+    prompt::Prompt,
+};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+use std::sync::{Arc, Mutex};
+
+// how hard a clicked element gets poked
+const CLICK_IMPULSE: f32 = 15.0;
+
+#[derive(Default)]
+pub struct MouseState {
+    // where the selection started if we're dragging across the prompt
+    drag_anchor: Option<usize>,
+}
+
+enum Target {
+    Prompt(usize),
+    Link(Strand, usize),
+    Nothing,
+}
+
+// what's under the mouse
+fn target_at(chain: &Chain, prompt: &Prompt, column: u16, row: u16) -> Target {
+    let Some(line) = row.checked_sub(chain.top_row).filter(|l| *l < chain.rows) else {
+        return Target::Nothing;
+    };
+
+    // the above chain's on the first row (if there is one), everything else is pushed down by it
+    let top = chain.input_row();
+    let strands = match line == top {
+        true => [Strand::Right, Strand::Main].as_slice(),
+        false if line == 0 => [Strand::Above].as_slice(),
+        false => [].as_slice(),
+    };
+    for &strand in strands {
+        for (i, link) in chain.links_of(strand).iter().enumerate() {
+            let start = link.mass.position.round() as i32;
+            let inside = (start..start + link.mass.width as i32).contains(&(column as i32));
+            if inside && !matches!(link.element, ElementType::Prompt(_)) {
+                return Target::Link(strand, i);
+            }
+        }
+    }
+    let Some(line) = line.checked_sub(top) else {
+        return Target::Nothing;
+    };
+
+    // anywhere past the start of the prompt counts, so clicking off the end of a line goes to the end of it
+    let prompt_start = chain
+        .links
+        .iter()
+        .find(|link| matches!(link.element, ElementType::Prompt(_)))
+        .map(|link| link.mass.position.round().max(0.0) as u16);
+    match prompt_start {
+        Some(start) if column >= start || line > 0 => Target::Prompt(
+            // (the input might be scrolled sideways)
+            prompt.position_at(
+                line as usize,
+                (column.saturating_sub(start) + chain.scroll) as usize,
+            ),
+        ),
+        _ => Target::Nothing,
+    }
+}
+
+impl MouseState {
+    // clicking puts the cursor down, dragging selects, and clicking an element pokes it & hands back
+    // its on_click action (if it has one)
+    pub fn handle(
+        &mut self,
+        event: &MouseEvent,
+        prompt: &mut Prompt,
+        chain: &Arc<Mutex<Chain>>,
+    ) -> Option<Action> {
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let mut chain = chain.lock().unwrap();
+                self.drag_anchor = None;
+                match target_at(&chain, prompt, event.column, event.row) {
+                    Target::Prompt(position) => {
+                        prompt.selection_start = None;
+                        prompt.move_to(position);
+                        self.drag_anchor = Some(position);
+                        None
+                    }
+                    Target::Link(strand, i) => {
+                        let link = &mut chain.links_of_mut(strand)[i];
+                        link.mass.velocity += CLICK_IMPULSE;
+                        link.element.on_click()
+                    }
+                    Target::Nothing => None,
+                }
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let anchor = self.drag_anchor?;
+                let chain = chain.lock().unwrap();
+                if let Target::Prompt(position) = target_at(&chain, prompt, event.column, event.row)
+                {
+                    prompt.edits.break_group();
+                    prompt.selection_start = Some(anchor);
+                    prompt.cursor_position = position;
+                }
+                None
+            }
+            MouseEventKind::Up(MouseButton::Left) => {
+                self.drag_anchor = None;
+                // just a click, not a drag
+                if prompt.selection_start == Some(prompt.cursor_position) {
+                    prompt.selection_start = None;
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+}
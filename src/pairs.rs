@@ -0,0 +1,103 @@
+use crate::evaluate::Token;
+
+// (opener, closer). quotes close themselves
+pub const PAIRS: &[(char, char)] = &[
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+];
+
+pub fn closer_for(c: char) -> Option<char> {
+    PAIRS.iter().find(|(o, _)| *o == c).map(|(_, cl)| *cl)
+}
+
+pub fn is_closer(c: char) -> bool {
+    PAIRS.iter().any(|(_, cl)| *cl == c)
+}
+
+#[derive(Default, Debug, PartialEq)]
+pub struct BracketHighlight {
+    // the bracket next to the cursor & the one it goes with
+    pub matching: Option<(usize, usize)>,
+    // brackets that don't have a partner
+    pub unbalanced: Vec<usize>,
+}
+
+impl BracketHighlight {
+    pub fn is_matching(&self, position: usize) -> bool {
+        self.matching
+            .is_some_and(|(a, b)| a == position || b == position)
+    }
+
+    pub fn is_unbalanced(&self, position: usize) -> bool {
+        self.unbalanced.contains(&position)
+    }
+}
+
+// brackets inside of quotes don't count, the tokenizer already knows where those are
+pub fn highlight_brackets(prompt: &str, tokens: &[Token], cursor: usize) -> BracketHighlight {
+    // from the first quote to the end of the token (quotes can start partway through, like foo"bar")
+    let quoted = |position: usize| {
+        tokens.iter().any(|t| {
+            prompt[t.start..t.end]
+                .find(['"', '\'', '`'])
+                .is_some_and(|q| (t.start + q..t.end).contains(&position))
+        })
+    };
+
+    let mut highlight = BracketHighlight::default();
+    let mut pairs = Vec::new();
+    let mut open: Vec<(usize, char)> = Vec::new();
+    for (position, c) in prompt.char_indices() {
+        if !matches!(c, '(' | '[' | '{' | ')' | ']' | '}') || quoted(position) {
+            continue;
+        }
+        if let Some(closer) = closer_for(c) {
+            open.push((position, closer));
+        } else if open.last().is_some_and(|(_, closer)| *closer == c) {
+            let (start, _) = open.pop().unwrap();
+            pairs.push((start, position));
+        } else {
+            highlight.unbalanced.push(position);
+        }
+    }
+    highlight.unbalanced.extend(open.iter().map(|(p, _)| p));
+    highlight.unbalanced.sort();
+
+    // whatever's under the cursor first, then whatever's right behind it
+    let behind = prompt[..cursor]
+        .chars()
+        .next_back()
+        .map(|c| cursor - c.len_utf8());
+    highlight.matching = [Some(cursor), behind]
+        .into_iter()
+        .flatten()
+        .find_map(|at| pairs.iter().find(|(a, b)| *a == at || *b == at).copied());
+
+    highlight
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{evaluate::tokenize, pairs::highlight_brackets};
+
+    #[test]
+    fn matching_and_unbalanced() {
+        let prompt = "echo (a [b] \"(\") }";
+        let tokens = tokenize(prompt);
+
+        let highlight = highlight_brackets(prompt, &tokens, 5);
+        assert_eq!(highlight.matching, Some((5, 15)));
+        assert_eq!(highlight.unbalanced, vec![17]);
+
+        // right after the ]
+        let highlight = highlight_brackets(prompt, &tokens, 11);
+        assert_eq!(highlight.matching, Some((8, 10)));
+
+        let highlight = highlight_brackets(prompt, &tokens, 2);
+        assert_eq!(highlight.matching, None);
+    }
+}
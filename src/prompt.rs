@@ -1,58 +1,331 @@
-use crate::misc_types::Direction;
-
+use crate::{
+    evaluate::{Token, Tokenizer, tokenize},
+    misc_types::Direction,
+    pairs::{closer_for, is_closer},
+    undo::{EditKind, Snapshot, UndoStack},
+    vi::ViMode,
+};
+use std::time::Instant;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+use unicode_width::UnicodeWidthStr;
+
+// cursor_position & selection_start are BYTE positions into prompt, and always sit on a grapheme
+// boundary. use cursor_column() for where the cursor actually shows up on screen.
 #[derive(Default)]
 pub struct Prompt {
-    pub prompt: String,
-    pub cursor_position: u16,
-    pub selection_start: Option<u16>, // if None, then there is no selection
+    prompt: String,
+    pub cursor_position: usize,
+    pub selection_start: Option<usize>, // if None, then there is no selection
+    pub edits: UndoStack,
+    pub vi_mode: Option<ViMode>, // None if vi mode is off
+    pub auto_pair: bool,
+    // the last abbreviation that got expanded & when, so it can flash for a moment
+    pub expanded: Option<(usize, usize, Instant)>,
+    // what the last completion offered (with descriptions), until the text changes again
+    completions: Option<String>,
+    // goes up by one every time the text (or the completions) changes, so other stuff can tell when to redo their work
+    pub revision: u64,
+    // always matches tokenize(&prompt), but only the bit around each edit gets redone
+    tokens: Vec<Token>,
+    // where auto pairing put closers in, the only ones typing over & pair backspacing touch
+    auto_closers: Vec<usize>,
+}
+
+pub enum WordCase {
+    Upper,
+    Lower,
+    Capitalized,
+}
+
+// how many terminal columns a string takes up
+pub fn display_width(s: &str) -> usize {
+    s.graphemes(true).map(|g| g.width()).sum()
 }
 
 impl Prompt {
+    // empty everything out for the next command (vi mode stays on, but goes back to insert)
+    pub fn reset(&mut self) {
+        *self = Prompt {
+            vi_mode: self.vi_mode.map(|_| ViMode::Insert),
+            auto_pair: self.auto_pair,
+            ..Default::default()
+        };
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            prompt: self.prompt.clone(),
+            cursor_position: self.cursor_position,
+            selection_start: self.selection_start,
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        self.prompt = snapshot.prompt;
+        self.tokens = tokenize(&self.prompt);
+        // (no telling which closers were typed by hand in there)
+        self.auto_closers.clear();
+        self.expanded = None;
+        self.completions = None;
+        self.revision += 1;
+        self.cursor_position = snapshot.cursor_position;
+        self.selection_start = snapshot.selection_start;
+    }
+
+    // (the text is only ever changed through splice, so the tokens can't fall behind)
+    pub fn text(&self) -> &str {
+        &self.prompt
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn completions(&self) -> Option<&str> {
+        self.completions.as_deref()
+    }
+
+    // (bumps the revision too, otherwise reactive elements would never notice)
+    pub fn show_completions(&mut self, completions: Option<String>) {
+        self.completions = completions;
+        self.revision += 1;
+    }
+
+    // EVERY change to the text goes through here so the tokens keep up
+    fn splice(&mut self, start: usize, end: usize, text: &str) {
+        self.prompt.replace_range(start..end, text);
+        self.completions = None;
+        self.revision += 1;
+        let delta = text.len() as isize - (end - start) as isize;
+        self.auto_closers.retain(|&at| at < start || at >= end);
+        for at in self.auto_closers.iter_mut().filter(|at| **at >= end) {
+            *at = (*at as isize + delta) as usize;
+        }
+        // the expanded abbreviation moves along with the text around it, editing it stops the flash
+        if let Some((from, to, when)) = self.expanded {
+            let shift = |at: usize| (at as isize + delta) as usize;
+            self.expanded = if start >= to {
+                Some((from, to, when))
+            } else if end <= from {
+                Some((shift(from), shift(to), when))
+            } else {
+                None
+            };
+        }
+        self.retokenize(start, end, start + text.len());
+    }
+
+    // [start, old_end) in the old text is now [start, new_end).
+    // tokens that end well before the edit stay put, then we tokenize from there until we land on the
+    // start of an old token again (past the edit), after which the rest are the old ones shifted over
+    fn retokenize(&mut self, start: usize, old_end: usize, new_end: usize) {
+        // one character of slack since && needs to look ahead
+        let kept = self.tokens.partition_point(|t| t.end + 1 < start);
+        let resume = kept.checked_sub(1).map_or(0, |i| self.tokens[i].end);
+        let mut rest = self.tokens.split_off(kept);
+        let delta = new_end as isize - old_end as isize;
+
+        let mut old = rest
+            .iter()
+            .position(|t| t.start >= old_end)
+            .unwrap_or(rest.len());
+        for token in Tokenizer::new(&self.prompt, resume) {
+            if token.start >= new_end {
+                while old < rest.len() && (rest[old].start as isize + delta) < token.start as isize
+                {
+                    old += 1;
+                }
+                if old < rest.len() && rest[old].start as isize + delta == token.start as isize {
+                    self.tokens.extend(rest.drain(old..).map(|mut t| {
+                        t.start = (t.start as isize + delta) as usize;
+                        t.end = (t.end as isize + delta) as usize;
+                        t
+                    }));
+                    return;
+                }
+            }
+            self.tokens.push(token);
+        }
+    }
+
+    // start of the grapheme before position (or 0)
+    pub fn previous_boundary(&self, position: usize) -> usize {
+        let mut cursor = GraphemeCursor::new(position, self.prompt.len(), true);
+        cursor
+            .prev_boundary(&self.prompt, 0)
+            .ok()
+            .flatten()
+            .unwrap_or(0)
+    }
+
+    // end of the grapheme after position (or the end of the prompt)
+    pub fn next_boundary(&self, position: usize) -> usize {
+        let mut cursor = GraphemeCursor::new(position, self.prompt.len(), true);
+        cursor
+            .next_boundary(&self.prompt, 0)
+            .ok()
+            .flatten()
+            .unwrap_or(self.prompt.len())
+    }
+
+    // start & end of the line the cursor is on (the end doesn't include the newline)
+    pub fn line_start(&self) -> usize {
+        self.prompt[..self.cursor_position]
+            .rfind('\n')
+            .map_or(0, |i| i + 1)
+    }
+
+    pub fn line_end(&self) -> usize {
+        self.prompt[self.cursor_position..]
+            .find('\n')
+            .map_or(self.prompt.len(), |i| self.cursor_position + i)
+    }
+
+    // which line the cursor is on, starting at 0
+    pub fn cursor_line(&self) -> usize {
+        self.prompt[..self.cursor_position].matches('\n').count()
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.prompt.matches('\n').count() + 1
+    }
+
+    // the column the cursor is on, relative to the start of its line
+    pub fn cursor_column(&self) -> usize {
+        display_width(&self.prompt[self.line_start()..self.cursor_position])
+    }
+
+    // how many columns the first line takes up, the rest go underneath the chain
+    pub fn width(&self) -> usize {
+        display_width(self.prompt.lines().next().unwrap_or(""))
+    }
+
+    // the position that's showing at (line, column), or the closest one to it
+    pub fn position_at(&self, line: usize, column: usize) -> usize {
+        let mut line_start = 0;
+        for l in self.prompt.split('\n').take(line) {
+            line_start += l.len() + 1;
+        }
+        // past the last line just means the end
+        if line_start > self.prompt.len() {
+            return self.prompt.len();
+        }
+
+        // walk along the line until we'd go past the column
+        let text = self.prompt[line_start..].split('\n').next().unwrap();
+        let mut position = line_start;
+        let mut width = 0;
+        for (i, g) in text.grapheme_indices(true) {
+            width += g.width();
+            if width > column {
+                break;
+            }
+            position = line_start + i + g.len();
+        }
+        position
+    }
+
+    // go to the same column on the line above/below, returns true if there isn't one
+    pub fn move_line(&mut self, up: bool) -> bool {
+        let line = self.cursor_line();
+        if (up && line == 0) || (!up && line + 1 == self.line_count()) {
+            return true;
+        }
+        let target = if up { line - 1 } else { line + 1 };
+
+        self.edits.break_group();
+        self.selection_start = None;
+        self.cursor_position = self.position_at(target, self.cursor_column());
+        false
+    }
+
+    // remember what the prompt looked like before an edit so it can be undone
+    pub fn record_edit(&mut self, kind: EditKind) {
+        let before = self.snapshot();
+        self.edits.record(kind, before);
+    }
+
+    // returns whether to "bump" or not (nothing to undo)
+    pub fn undo(&mut self) -> bool {
+        let current = self.snapshot();
+        match self.edits.undo(current) {
+            Some(previous) => {
+                self.restore(previous);
+                false
+            }
+            None => true,
+        }
+    }
+
+    // returns whether to "bump" or not (nothing to redo)
+    pub fn redo(&mut self) -> bool {
+        let current = self.snapshot();
+        match self.edits.redo(current) {
+            Some(next) => {
+                self.restore(next);
+                false
+            }
+            None => true,
+        }
+    }
+
     pub fn start_selection(&mut self) {
         self.selection_start = Some(self.cursor_position);
     }
 
     // check if a selection even exists, and if so whether the position in the prompt is within aformentioned selection
-    pub fn position_is_in_selection(&self, position: u16) -> bool {
-        match self.selection_start {
-            Some(start) => {
-                if (self.cursor_position as i16) - (start as i16) >= 0 {
-                    self.cursor_position >= position && start <= position
-                } else {
-                    start >= position && self.cursor_position <= position
-                }
-            }
+    pub fn position_is_in_selection(&self, position: usize) -> bool {
+        match self.selection_range() {
+            Some((start, end)) => start <= position && position < end,
             None => false,
         }
     }
 
-    pub fn find_skippable_in_direction(&self, direction: Direction) -> u16 {
-        let (l, r) = self
-            .prompt
-            .split_at_checked(self.cursor_position as usize)
-            .unwrap();
+    pub fn select_all(&mut self) {
+        self.edits.break_group();
+        self.selection_start = Some(0);
+        self.cursor_position = self.prompt.len();
+    }
+
+    // get rid of the selected text without touching the undo stack, returns whether anything was there
+    fn remove_selection(&mut self) -> bool {
+        let Some((start, end)) = self.selection_range() else {
+            return false;
+        };
+        self.selection_start = None;
+        if start == end {
+            return false;
+        }
+
+        self.splice(start, end, "");
+        self.cursor_position = start;
+        true
+    }
+
+    pub fn find_skippable_in_direction(&self, direction: Direction) -> usize {
+        let (l, r) = self.prompt.split_at_checked(self.cursor_position).unwrap();
         match direction {
             Direction::Left => {
-                let mut iter = l.chars().rev().enumerate();
+                let mut iter = l.char_indices().rev();
                 _ = iter.next();
                 for (i, c) in iter {
                     // check if THIS character is "skippable", if it is, set cursor_pos and return here
                     match c {
                         '/' | ' ' | '.' | ',' | '\'' | '"' => {
-                            return self.cursor_position - (i as u16);
+                            return i + c.len_utf8();
                         }
                         _ => {}
                     }
                 }
             }
             Direction::Right => {
-                let mut iter = r.chars().enumerate();
+                let mut iter = r.char_indices();
                 _ = iter.next();
                 for (i, c) in iter {
                     // same thing.. duplication... ):
                     match c {
                         '/' | ' ' | '.' | ',' | '\'' | '"' => {
-                            return i as u16 + self.cursor_position;
+                            return i + self.cursor_position;
                         }
                         _ => {}
                     }
@@ -63,50 +336,44 @@ impl Prompt {
         // failed to find anything so return the start/end of the prompt
         match direction {
             Direction::Left => 0,
-            Direction::Right => self.prompt.len() as u16,
+            Direction::Right => self.prompt.len(),
         }
     }
 
     // move cursor forwards or backwards a "word" (actually a bit more than that)
     pub fn jump_in_direction(&mut self, direction: Direction) {
+        self.edits.break_group();
         let jump_to = self.find_skippable_in_direction(direction);
         self.cursor_position = jump_to;
     }
 
     // them funny skipping motions
     pub fn ctrl_backspace(&mut self) -> bool {
+        if self.selection_start.is_some() {
+            return self.delete_selection();
+        }
+
         let cut_position = self.find_skippable_in_direction(Direction::Left);
         if cut_position == self.cursor_position {
             return self.delete_character();
         };
 
-        let left_side = &self.prompt[0..cut_position as usize];
-        let right_side = &self.prompt[self.cursor_position as usize..];
-
-        self.prompt = format!("{}{}", left_side, right_side);
+        self.record_edit(EditKind::Deletion);
+        self.splice(cut_position, self.cursor_position, "");
         self.cursor_position = cut_position;
 
         false
     }
 
-    // move the cursor in the direction, space times
+    // move the cursor in the direction, space graphemes
     pub fn move_cursor(&mut self, space: u32, direction: Direction) {
-        // would it be cursed if we could cast direction into a i16?
-        let neg = match direction {
-            Direction::Left => -1,
-            Direction::Right => 1,
-        };
-
-        let new_position = self.cursor_position as i16 + (space as i16 * neg);
-        if new_position <= 0 {
-            self.cursor_position = 0;
-            return;
-        } else if new_position >= (self.prompt.len() as i16) {
-            self.cursor_position = self.prompt.len() as u16;
-            return;
+        self.edits.break_group();
+        for _ in 0..space {
+            self.cursor_position = match direction {
+                Direction::Left => self.previous_boundary(self.cursor_position),
+                Direction::Right => self.next_boundary(self.cursor_position),
+            };
         }
-
-        self.cursor_position = new_position as u16
     }
 
     // handle left & right
@@ -116,6 +383,17 @@ impl Prompt {
             self.start_selection();
         }
 
+        // plain arrows collapse the selection onto whichever end they point at
+        if !shift && let Some((start, end)) = self.selection_range() {
+            self.edits.break_group();
+            self.selection_start = None;
+            self.cursor_position = match direction {
+                Direction::Left => start,
+                Direction::Right => end,
+            };
+            return false;
+        }
+
         let prev = self.cursor_position;
         if ctrl {
             self.jump_in_direction(direction);
@@ -128,33 +406,100 @@ impl Prompt {
 
     // insert a character at the cursors current position
     pub fn insert_character(&mut self, character: char) {
-        // if we're at or past the end of the string just append
-        if self.cursor_position >= self.prompt.len() as u16 {
-            self.prompt.push(character);
-            self.cursor_position = self.prompt.len() as u16;
-        } else {
-            self.prompt
-                .insert((self.cursor_position) as usize, character);
-            self.cursor_position += 1;
+        self.record_edit(EditKind::Typing);
+        // typing over a selection replaces it
+        self.remove_selection();
+        let at = self.cursor_position.min(self.prompt.len());
+        self.splice(at, at, character.encode_utf8(&mut [0; 4]));
+        self.cursor_position = at + character.len_utf8();
+    }
+
+    // swap out part of the prompt for something else, leaving the cursor right after it
+    pub fn replace_range(&mut self, start: usize, end: usize, text: &str, kind: EditKind) {
+        self.record_edit(kind);
+        self.selection_start = None;
+        self.splice(start, end, text);
+        self.cursor_position = start + text.len();
+    }
+
+    // what typing a character does: insert_character, plus closing quotes & brackets when auto_pair is on
+    pub fn type_character(&mut self, character: char) {
+        if !self.auto_pair || self.selection_start.is_some() {
+            return self.insert_character(character);
+        }
+
+        let next = self.prompt[self.cursor_position..].chars().next();
+        let previous = self.prompt[..self.cursor_position].chars().next_back();
+
+        // we put one there already, just step over it
+        if next == Some(character) && self.auto_closers.contains(&self.cursor_position) {
+            let at = self.cursor_position;
+            self.auto_closers.retain(|&a| a != at);
+            self.cursor_position += character.len_utf8();
+            return;
+        }
+
+        let Some(closer) = closer_for(character) else {
+            return self.insert_character(character);
+        };
+        // don't pair up in the middle of a word (don't, foo(bar...)
+        let next_ok = next.is_none_or(|n| n.is_whitespace() || is_closer(n));
+        let previous_ok = character != closer || previous.is_none_or(|p| !p.is_alphanumeric());
+        self.insert_character(character);
+        if next_ok && previous_ok {
+            let at = self.cursor_position;
+            self.splice(at, at, closer.encode_utf8(&mut [0; 4]));
+            self.auto_closers.push(at);
         }
     }
 
-    // delete character at cursor position
+    // put a whole string in at the cursor
+    pub fn insert_str(&mut self, text: &str, kind: EditKind) {
+        self.record_edit(kind);
+        self.remove_selection();
+        let at = self.cursor_position.min(self.prompt.len());
+        self.splice(at, at, text);
+        self.cursor_position = at + text.len();
+    }
+
+    // the selected part of the prompt as (start, end), if anything is selected
+    pub fn selection_range(&self) -> Option<(usize, usize)> {
+        let start = self.selection_start?;
+        let cursor = self.cursor_position;
+        let end = start.max(cursor).min(self.prompt.len());
+        // vi's visual mode includes whatever is under the cursor
+        let end = match self.vi_mode {
+            Some(ViMode::Visual) => self.next_boundary(end),
+            _ => end,
+        };
+        Some((start.min(cursor), end))
+    }
+
+    // cut a piece out of the prompt and hand it back, the cursor ends up where the piece was
+    pub fn kill_range(&mut self, start: usize, end: usize) -> String {
+        if start >= end {
+            return String::new();
+        }
+
+        self.record_edit(EditKind::Deletion);
+        let killed = self.prompt[start..end].to_string();
+        self.splice(start, end, "");
+        self.cursor_position = start;
+        self.selection_start = None;
+        killed
+    }
+
+    // delete the grapheme behind the cursor
     // returns whether to "bump" or not
     pub fn delete_character(&mut self) -> bool {
         if self.prompt.is_empty() || self.cursor_position == 0 {
             return true;
         }
 
-        if self.cursor_position >= self.prompt.len() as u16 {
-            self.prompt.pop();
-        } else {
-            self.prompt.remove((self.cursor_position - 1) as usize);
-        }
-
-        if self.cursor_position != 0 {
-            self.cursor_position -= 1;
-        }
+        self.record_edit(EditKind::Deletion);
+        let start = self.previous_boundary(self.cursor_position);
+        self.splice(start, self.cursor_position, "");
+        self.cursor_position = start;
 
         false
     }
@@ -162,37 +507,131 @@ impl Prompt {
     // delete whatever is selected at the current moment (will panic if there is no selection)
     // returns whether to "bump" or not
     pub fn delete_selection(&mut self) -> bool {
-        let selection = self.selection_start.expect(
+        let (start, end) = self.selection_range().expect(
             "i should've checked whether a selection EXISTED before calling this function.",
         );
 
-        // might need this more often
-        let (smaller, bigger) = if selection > self.cursor_position {
-            (self.cursor_position as usize, selection as usize)
-        } else {
-            (selection as usize, self.cursor_position as usize)
-        };
+        // an empty selection is just a cursor, so there's nothing to delete
+        if start == end {
+            self.selection_start = None;
+            return true;
+        }
 
-        let mut bump = false;
+        self.record_edit(EditKind::Deletion);
+        self.remove_selection();
 
-        let first = if smaller > 0 {
-            &self.prompt[0..smaller - 1]
-        } else {
-            bump = true;
-            ""
-        };
+        false
+    }
 
-        let second = if bigger < self.prompt.len() {
-            &self.prompt[bigger + 1..]
-        } else {
-            ""
-        };
+    // put the cursor somewhere without touching the selection, returns whether to "bump" or not
+    pub fn move_to(&mut self, position: usize) -> bool {
+        self.edits.break_group();
+        let moved = position != self.cursor_position;
+        self.cursor_position = position.min(self.prompt.len());
+        !moved
+    }
 
-        self.selection_start = None;
-        self.cursor_position = (smaller - 1) as u16;
-        self.prompt = format!("{}{}", first, second);
+    // where an emacs style word motion lands. words here are just runs of letters & numbers, unlike
+    // find_skippable_in_direction which is for the ctrl+arrow jumps.
+    pub fn word_in_direction(&self, direction: Direction) -> usize {
+        let is_word = |g: &str| g.chars().next().is_some_and(|c| c.is_alphanumeric());
+        match direction {
+            Direction::Left => {
+                let before = &self.prompt[..self.cursor_position];
+                let mut graphemes = before.grapheme_indices(true).rev().peekable();
+                while graphemes.next_if(|(_, g)| !is_word(g)).is_some() {}
+                let mut start = graphemes.peek().map(|(i, g)| i + g.len()).unwrap_or(0);
+                while let Some((i, _)) = graphemes.next_if(|(_, g)| is_word(g)) {
+                    start = i;
+                }
+                start
+            }
+            Direction::Right => {
+                let after = &self.prompt[self.cursor_position..];
+                let mut graphemes = after.grapheme_indices(true).peekable();
+                while graphemes.next_if(|(_, g)| !is_word(g)).is_some() {}
+                while graphemes.next_if(|(_, g)| is_word(g)).is_some() {}
+                graphemes
+                    .peek()
+                    .map(|(i, _)| self.cursor_position + i)
+                    .unwrap_or(self.prompt.len())
+            }
+        }
+    }
+
+    // delete the grapheme under the cursor (the delete key)
+    // returns whether to "bump" or not
+    pub fn delete_forward(&mut self) -> bool {
+        if self.selection_start.is_some() {
+            return self.delete_selection();
+        }
+        if self.cursor_position >= self.prompt.len() {
+            return true;
+        }
+
+        self.record_edit(EditKind::Deletion);
+        let end = self.next_boundary(self.cursor_position);
+        self.splice(self.cursor_position, end, "");
+
+        false
+    }
+
+    // swap the graphemes on either side of the cursor, at the end of the line it swaps the last two
+    // returns whether to "bump" or not
+    pub fn transpose(&mut self) -> bool {
+        let mut middle = self.cursor_position;
+        if middle >= self.prompt.len() {
+            middle = self.previous_boundary(self.prompt.len());
+        }
+        if middle == 0 {
+            return true;
+        }
+
+        let start = self.previous_boundary(middle);
+        let end = self.next_boundary(middle);
+        self.record_edit(EditKind::Other);
+        let swapped = format!(
+            "{}{}",
+            &self.prompt[middle..end],
+            &self.prompt[start..middle]
+        );
+        self.splice(start, end, &swapped);
+        self.cursor_position = end;
+
+        false
+    }
+
+    // upcase/downcase/capitalize from the cursor to the end of the next word, like alt+u/l/c
+    // returns whether to "bump" or not
+    pub fn change_word_case(&mut self, case: WordCase) -> bool {
+        let end = self.word_in_direction(Direction::Right);
+        if end == self.cursor_position {
+            return true;
+        }
+
+        let word = &self.prompt[self.cursor_position..end];
+        let changed: String = match case {
+            WordCase::Upper => word.to_uppercase(),
+            WordCase::Lower => word.to_lowercase(),
+            WordCase::Capitalized => {
+                let mut seen_letter = false;
+                word.chars()
+                    .flat_map(|c| {
+                        let first = !seen_letter && c.is_alphanumeric();
+                        seen_letter |= c.is_alphanumeric();
+                        if first {
+                            c.to_uppercase().collect::<Vec<_>>()
+                        } else {
+                            c.to_lowercase().collect::<Vec<_>>()
+                        }
+                    })
+                    .collect()
+            }
+        };
 
-        bump
+        let start = self.cursor_position;
+        self.replace_range(start, end, &changed, EditKind::Other);
+        false
     }
 
     // handles all backspace logic
@@ -206,8 +645,255 @@ impl Prompt {
             return true;
         }
 
+        // backspacing an empty pair gets rid of both halves
+        let previous = self.prompt[..self.cursor_position].chars().next_back();
+        let next = self.prompt[self.cursor_position..].chars().next();
+        if self.auto_pair
+            && let Some(opener) = previous
+            && next.is_some_and(|n| closer_for(opener) == Some(n))
+            && self.auto_closers.contains(&self.cursor_position)
+        {
+            self.record_edit(EditKind::Deletion);
+            let start = self.cursor_position - opener.len_utf8();
+            self.splice(start, self.cursor_position + 1, "");
+            self.cursor_position = start;
+            return false;
+        }
+
         self.delete_character();
 
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{evaluate::tokenize, misc_types::Direction, prompt::Prompt, undo::EditKind};
+
+    #[test]
+    fn undo_groups_typing_and_restores_cursor() {
+        let mut p = Prompt::default();
+        for c in "echo hello".chars() {
+            p.insert_character(c);
+        }
+        p.move_cursor(5, Direction::Left);
+        for c in "there ".chars() {
+            p.insert_character(c);
+        }
+        assert_eq!(p.prompt, "echo there hello");
+
+        p.ctrl_backspace();
+        assert_eq!(p.prompt, "echo hello");
+
+        assert!(!p.undo());
+        assert_eq!(p.prompt, "echo there hello");
+        assert_eq!(p.cursor_position, 11);
+
+        // all of "there " goes at once
+        assert!(!p.undo());
+        assert_eq!(p.prompt, "echo hello");
+        assert_eq!(p.cursor_position, 5);
+
+        assert!(!p.undo());
+        assert_eq!(p.prompt, "");
+        assert!(p.undo());
+
+        assert!(!p.redo());
+        assert!(!p.redo());
+        assert_eq!(p.prompt, "echo there hello");
+        assert_eq!(p.cursor_position, 11);
+
+        // a new edit throws away whatever could've been redone
+        p.insert_character('!');
+        assert!(p.redo());
+    }
+
+    fn type_in(p: &mut Prompt, s: &str) {
+        for c in s.chars() {
+            p.insert_character(c);
+        }
+    }
+
+    #[test]
+    fn cjk_is_two_columns_wide() {
+        let mut p = Prompt::default();
+        type_in(&mut p, "echo 你好");
+        assert_eq!(p.cursor_position, "echo 你好".len());
+        assert_eq!(p.cursor_column(), 9);
+
+        p.move_cursor(1, Direction::Left);
+        assert_eq!(p.cursor_column(), 7);
+        p.insert_character('x');
+        assert_eq!(p.prompt, "echo 你x好");
+
+        p.move_cursor(1, Direction::Right);
+        assert!(!p.backspace());
+        assert_eq!(p.prompt, "echo 你x");
+        assert_eq!(p.width(), 8);
+    }
+
+    #[test]
+    fn combining_marks_stay_with_their_letter() {
+        let mut p = Prompt::default();
+        // e + combining acute accent
+        type_in(&mut p, "cafe\u{301}!");
+        assert_eq!(p.width(), 5);
+
+        p.move_cursor(1, Direction::Left);
+        p.move_cursor(1, Direction::Left);
+        assert_eq!(p.cursor_position, 3);
+        assert_eq!(p.cursor_column(), 3);
+
+        p.move_cursor(1, Direction::Right);
+        assert!(!p.backspace());
+        assert_eq!(p.prompt, "caf!");
+    }
+
+    #[test]
+    fn emoji_zwj_sequence_is_one_grapheme() {
+        let family = "👨\u{200D}👩\u{200D}👧";
+        let mut p = Prompt::default();
+        type_in(&mut p, "a");
+        type_in(&mut p, family);
+        type_in(&mut p, "b");
+        assert_eq!(p.width(), 4);
+
+        p.move_cursor(2, Direction::Left);
+        assert_eq!(p.cursor_position, 1);
+        assert_eq!(p.cursor_column(), 1);
+
+        p.move_cursor(1, Direction::Right);
+        assert_eq!(p.cursor_position, 1 + family.len());
+        assert_eq!(p.cursor_column(), 3);
+
+        assert!(!p.backspace());
+        assert_eq!(p.prompt, "ab");
+    }
+
+    #[test]
+    fn prompts_bigger_than_a_u16() {
+        let mut p = Prompt::default();
+        p.insert_str(&"é".repeat(40_000), crate::undo::EditKind::Paste);
+        assert_eq!(p.cursor_position, 80_000);
+        assert_eq!(p.cursor_column(), 40_000);
+        p.move_cursor(1, Direction::Left);
+        assert_eq!(p.cursor_position, 79_998);
+    }
+
+    #[test]
+    fn selection_replace_and_delete() {
+        let mut p = Prompt::default();
+        type_in(&mut p, "hello world");
+
+        // selecting right up to the start used to underflow
+        p.cursor_position = 5;
+        for _ in 0..5 {
+            p.horiziontal_arrow(Direction::Left, true, false);
+        }
+        assert_eq!(p.selection_range(), Some((0, 5)));
+        assert!(p.position_is_in_selection(0));
+        assert!(!p.position_is_in_selection(5));
+        assert!(!p.backspace());
+        assert_eq!(p.prompt, " world");
+        assert_eq!(p.cursor_position, 0);
+
+        p.select_all();
+        type_in(&mut p, "yo");
+        assert_eq!(p.prompt, "yo");
+        assert_eq!(p.selection_start, None);
+
+        // plain arrows just collapse the selection
+        p.select_all();
+        assert!(!p.horiziontal_arrow(Direction::Left, false, false));
+        assert_eq!((p.cursor_position, p.selection_start), (0, None));
+    }
+
+    #[test]
+    fn auto_pairing() {
+        let mut p = Prompt {
+            auto_pair: true,
+            ..Default::default()
+        };
+        for c in "echo (\"hi\") don't".chars() {
+            p.type_character(c);
+        }
+        assert_eq!(p.prompt, "echo (\"hi\") don't");
+
+        p.type_character(' ');
+        p.type_character('[');
+        assert_eq!(p.prompt, "echo (\"hi\") don't []");
+        assert!(!p.backspace());
+        assert_eq!(p.prompt, "echo (\"hi\") don't ");
+
+        // one undo takes back the whole pair
+        p.type_character('{');
+        assert!(!p.undo());
+        assert_eq!(p.prompt, "echo (\"hi\") don't ");
+
+        // closers that were typed by hand get left alone
+        type_in(&mut p, "()");
+        p.cursor_position -= 1;
+        p.type_character(')');
+        assert_eq!(p.prompt, "echo (\"hi\") don't ())");
+        p.cursor_position = p.prompt.len() - 2;
+        assert!(!p.backspace());
+        assert_eq!(p.prompt, "echo (\"hi\") don't ))");
+    }
+
+    #[test]
+    fn multi_line_positions() {
+        let mut p = Prompt::default();
+        type_in(&mut p, "echo 日本\nab\nlonger line");
+
+        assert_eq!(p.position_at(0, 7), 8);
+        // halfway through a wide character doesn't count as past it
+        assert_eq!(p.position_at(0, 6), 5);
+        assert_eq!(p.position_at(1, 10), 14);
+        assert_eq!(p.position_at(5, 0), p.prompt.len());
+
+        p.cursor_position = p.position_at(2, 5);
+        assert!(!p.move_line(true));
+        assert_eq!((p.cursor_line(), p.cursor_column()), (1, 2));
+        assert!(!p.move_line(true));
+        assert_eq!((p.cursor_line(), p.cursor_column()), (0, 2));
+        assert!(p.move_line(true));
+    }
+
+    #[test]
+    fn tokens_keep_up_with_edits() {
+        // a little xorshift so this is the same every run
+        let mut seed = 0x2545f4914f6cdd1du64;
+        let mut random = |n: usize| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % n as u64) as usize
+        };
+        let pieces = [
+            "echo", " ", "-", "--", "\"", "'", "|", "&", "&&", "./dir", "x", "\n", "日本",
+        ];
+
+        let mut p = Prompt::default();
+        for _ in 0..2000 {
+            let mut at = random(p.prompt.len() + 1);
+            while !p.prompt.is_char_boundary(at) {
+                at -= 1;
+            }
+            p.cursor_position = at;
+            match random(4) {
+                0 => _ = p.kill_range(at, p.next_boundary(p.next_boundary(at))),
+                1 => _ = p.backspace(),
+                _ => p.insert_str(pieces[random(pieces.len())], EditKind::Typing),
+            }
+            assert_eq!(
+                p.tokens(),
+                tokenize(&p.prompt),
+                "after editing {:?}",
+                p.prompt
+            );
+        }
+
+        p.undo();
+        assert_eq!(p.tokens(), tokenize(&p.prompt));
+    }
+}
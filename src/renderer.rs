@@ -0,0 +1,274 @@
+use crate::{
+    color_depth::ColorDepth,
+    error::EASHError,
+    surface::{Cell, GridSurface, Style},
+};
+use crossterm::{
+    cursor::{MoveToColumn, MoveUp, SetCursorStyle},
+    queue,
+    style::{Print, SetBackgroundColor, SetForegroundColor, force_color_output},
+    terminal::{Clear, ClearType},
+};
+use std::io::{self, Write};
+use unicode_width::UnicodeWidthStr;
+
+// keeps track of how much went out to the terminal
+pub struct CountingWriter<W: Write> {
+    w: W,
+    pub count: usize,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.w.write(buf)?;
+        self.count += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+}
+
+// frames get drawn into a GridSurface, and this compares each one to the last one and only sends
+// the cells that changed. rows are relative to the chain just like on a Surface
+pub struct Renderer<W: Write> {
+    w: CountingWriter<W>,
+    previous: Option<GridSurface>,
+    // where the terminal's cursor is & what colors it's printing in. None if we don't know
+    row: u16,
+    column: Option<u16>,
+    style: Option<Style>,
+    cursor_style: Option<SetCursorStyle>,
+    // which terminal row the chain's first row is on, & how many there are. newlines at the bottom
+    // scroll everything up, chain included
+    pub top: u16,
+    pub height: u16,
+    // frames are drawn in whatever colors the config says, they get downgraded on the way out
+    depth: ColorDepth,
+    // how many bytes the last frame took, an unchanged frame should be basically nothing
+    pub frame_bytes: usize,
+}
+
+impl<W: Write> Renderer<W> {
+    pub fn new(w: W, depth: ColorDepth) -> Renderer<W> {
+        // crossterm leaves colors out by itself when NO_COLOR is set, but we already looked at it
+        // when working out the depth (and --color=always has to win over it)
+        if depth != ColorDepth::NoColor {
+            force_color_output(true);
+        }
+        Renderer {
+            w: CountingWriter { w, count: 0 },
+            previous: None,
+            row: 0,
+            column: None,
+            style: None,
+            cursor_style: None,
+            top: 0,
+            height: u16::MAX,
+            depth,
+            frame_bytes: 0,
+        }
+    }
+
+    // something else printed over the last frame, so the next one gets drawn from scratch.
+    // row is where the terminal's cursor is now, relative to where the chain is going to go
+    pub fn repaint(&mut self, row: u16) {
+        self.previous = None;
+        self.row = row;
+        self.column = None;
+        self.style = None;
+    }
+
+    fn move_to(&mut self, column: u16, row: u16) -> Result<(), EASHError> {
+        if row < self.row {
+            queue!(self.w, MoveUp(self.row - row))?;
+        }
+        // newlines instead of MoveDown so it scrolls at the bottom of the terminal
+        for r in self.row..row {
+            queue!(self.w, Print("\r\n"))?;
+            self.column = Some(0);
+            if self.top as u32 + r as u32 + 1 >= self.height as u32 {
+                self.top = self.top.saturating_sub(1);
+            }
+        }
+        self.row = row;
+        if self.column != Some(column) {
+            queue!(self.w, MoveToColumn(column))?;
+            self.column = Some(column);
+        }
+        Ok(())
+    }
+
+    // only send the half of the style that's different
+    fn set_style(&mut self, style: Style) -> Result<(), EASHError> {
+        let style = Style::new(
+            self.depth.downgrade(style.fg),
+            self.depth.downgrade(style.bg),
+        );
+        let current = self.style;
+        if current.is_none_or(|c| c.fg != style.fg) {
+            queue!(self.w, SetForegroundColor(style.fg))?;
+        }
+        if current.is_none_or(|c| c.bg != style.bg) {
+            queue!(self.w, SetBackgroundColor(style.bg))?;
+        }
+        self.style = Some(style);
+        Ok(())
+    }
+
+    pub fn present(&mut self, frame: GridSurface) -> Result<(), EASHError> {
+        self.w.count = 0;
+
+        // a different width means everything moved around, no point diffing
+        let previous = self.previous.take().filter(|p| p.width == frame.width);
+        if previous.is_none() {
+            self.move_to(0, 0)?;
+            queue!(self.w, Clear(ClearType::FromCursorDown))?;
+        }
+        // whatever isn't in the last frame is blank on screen
+        let old = previous.unwrap_or_else(|| GridSurface::new(frame.width));
+        let blank = Cell::default();
+
+        for (r, row) in frame.rows.iter().enumerate() {
+            let r = r as u16;
+            for (c, cell) in row.iter().enumerate() {
+                let c = c as u16;
+                // the right halves of wide characters get drawn along with the left
+                if cell.grapheme.is_empty() {
+                    continue;
+                }
+                let width = cell.grapheme.width() as u16;
+                let changed = (c..c + width.max(1)).any(|i| {
+                    frame
+                        .cell(i, r)
+                        .is_some_and(|new| new != old.cell(i, r).unwrap_or(&blank))
+                });
+                if !changed {
+                    continue;
+                }
+
+                self.move_to(c, r)?;
+                self.set_style(cell.style)?;
+                queue!(self.w, Print(&cell.grapheme))?;
+                self.column = Some(c + width);
+            }
+        }
+
+        // the last frame was taller, get rid of the leftovers
+        if old.rows.len() > frame.rows.len() {
+            self.move_to(0, frame.rows.len() as u16)?;
+            queue!(self.w, Clear(ClearType::FromCursorDown))?;
+        }
+
+        let (column, row) = frame.cursor;
+        self.move_to(column, row)?;
+        if let Some(style) = frame.cursor_style
+            && self.cursor_style != Some(style)
+        {
+            queue!(self.w, style)?;
+            self.cursor_style = Some(style);
+        }
+        self.w.flush()?;
+
+        self.frame_bytes = self.w.count;
+        self.previous = Some(frame);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        color_depth::ColorDepth,
+        renderer::Renderer,
+        surface::{AnsiSurface, GridSurface, Style, Surface},
+    };
+    use crossterm::style::{Color as ctColor, force_color_output};
+    use std::sync::Mutex;
+
+    // whether crossterm prints colors at all is global, so the tests that care take turns
+    static COLORS: Mutex<()> = Mutex::new(());
+
+    fn frame(text: &str) -> GridSurface {
+        let mut grid = GridSurface::new(40);
+        grid.set_style(Style::new(
+            ctColor::Rgb { r: 1, g: 2, b: 3 },
+            ctColor::Reset,
+        ))
+        .unwrap();
+        for (row, line) in text.split('\n').enumerate() {
+            grid.move_to(2, row as u16).unwrap();
+            for c in line.chars() {
+                grid.put(c.encode_utf8(&mut [0; 4])).unwrap();
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn only_changes_get_sent() {
+        let _colors = COLORS.lock().unwrap();
+        let mut renderer = Renderer::new(Vec::new(), ColorDepth::TrueColor);
+        renderer.present(frame("echo hello world")).unwrap();
+        let full = renderer.frame_bytes;
+
+        // the same thing again shouldn't need anything at all
+        renderer.present(frame("echo hello world")).unwrap();
+        assert_eq!(renderer.frame_bytes, 0);
+
+        renderer.w.w.clear();
+        renderer.present(frame("echo hellO world")).unwrap();
+        let written = String::from_utf8(renderer.w.w.clone()).unwrap();
+        assert!(renderer.frame_bytes < full / 2, "{written:?}");
+        assert!(written.contains('O') && !written.contains("echo"));
+
+        // a frame drawn the old way, all of it every time
+        let mut ansi = AnsiSurface::new(Vec::new());
+        ansi.clear().unwrap();
+        ansi.move_to(2, 0).unwrap();
+        ansi.set_style(Style::new(
+            ctColor::Rgb { r: 1, g: 2, b: 3 },
+            ctColor::Reset,
+        ))
+        .unwrap();
+        ansi.put("echo hellO world").unwrap();
+        assert!(renderer.frame_bytes * 3 < ansi.into_inner().len());
+    }
+
+    #[test]
+    fn colors_come_out_even_with_no_color_set() {
+        let _colors = COLORS.lock().unwrap();
+        // (crossterm only reads NO_COLOR the first time, so tell it directly too)
+        unsafe { std::env::set_var("NO_COLOR", "1") };
+        force_color_output(false);
+
+        // like `--color=always` with NO_COLOR set
+        let mut renderer = Renderer::new(Vec::new(), ColorDepth::Ansi256);
+        renderer.present(frame("hi")).unwrap();
+        let written = String::from_utf8(renderer.w.w.clone()).unwrap();
+        assert!(written.contains("\x1b[38;5;16m"), "{written:?}");
+    }
+
+    #[test]
+    fn scrolling_at_the_bottom_moves_the_top_up() {
+        let mut renderer = Renderer::new(Vec::new(), ColorDepth::TrueColor);
+        renderer.height = 10;
+        renderer.top = 8;
+        renderer.present(frame("one\ntwo\nthree")).unwrap();
+        assert_eq!(renderer.top, 7);
+    }
+
+    #[test]
+    fn shrinking_clears_the_leftover_rows() {
+        let mut renderer = Renderer::new(Vec::new(), ColorDepth::TrueColor);
+        renderer.present(frame("one\ntwo")).unwrap();
+        renderer.w.w.clear();
+        renderer.present(frame("one")).unwrap();
+        // the cursor is already on the leftover row, so clear it & go back up to the end of "one"
+        assert_eq!(
+            String::from_utf8(renderer.w.w.clone()).unwrap(),
+            "\x1b[1G\x1b[J\x1b[1A\x1b[6G"
+        );
+    }
+}
@@ -1,14 +1,12 @@
 use crate::{
-    error::EASHError, evaluate::{
-        Token, TokenType
-    }
+    error::EASHError,
+    evaluate::{Token, TokenType},
 };
 
 use std::process::{Child as PChild, Command as PCommand};
 
 pub struct Command {
     inner: PCommand,
-
 }
 
 // impl TryFrom<Vec<Token>> for Command {
@@ -20,11 +18,9 @@ pub struct Command {
 
 pub struct Child {
     inner: PChild,
-    command: Command
+    command: Command,
 }
 
 impl Child {
-    pub fn spawn() {
-        
-    }
-}
\ No newline at end of file
+    pub fn spawn() {}
+}
@@ -0,0 +1,128 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::Instant,
+};
+
+#[derive(Default)]
+struct Frames {
+    requested: bool,
+    // frames the render thread has started & finished, so redraw_now knows when its frame is out
+    started: u64,
+    finished: u64,
+    // the earliest anybody asked to be woken up at (timers and such)
+    wake_at: Option<Instant>,
+}
+
+// tells the render thread when there's actually something to draw, so it can sleep the rest of the time.
+// it gets woken up by input (main asks for a frame after every event), by springs that are still
+// moving, by the next frame of an animated glyph and by whoever calls wake_at
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    inner: Arc<(Mutex<Frames>, Condvar)>,
+}
+
+// the sooner of two maybe-times
+pub fn earliest(a: Option<Instant>, b: Option<Instant>) -> Option<Instant> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+impl Scheduler {
+    pub fn request_redraw(&self) {
+        let (frames, wake) = &*self.inner;
+        frames.lock().unwrap().requested = true;
+        wake.notify_all();
+    }
+
+    // draw at (or a little after) this point in time, even if nothing else happens
+    // TODO)) elements with TriggerType::Timed should use this once triggers are a thing
+    pub fn wake_at(&self, when: Instant) {
+        let (frames, wake) = &*self.inner;
+        let mut frames = frames.lock().unwrap();
+        frames.wake_at = earliest(frames.wake_at, Some(when));
+        wake.notify_all();
+    }
+
+    // ask for a frame and wait until one that started after asking is on screen
+    pub fn redraw_now(&self) {
+        let (frames, wake) = &*self.inner;
+        let mut frames = frames.lock().unwrap();
+        frames.requested = true;
+        let target = frames.started + 1;
+        wake.notify_all();
+        while frames.finished < target {
+            frames = wake.wait(frames).unwrap();
+        }
+    }
+
+    // (render thread) sleep until a frame was asked for or it's time for one
+    pub fn wait(&self, until: Option<Instant>) {
+        let (frames, wake) = &*self.inner;
+        let mut frames = frames.lock().unwrap();
+        loop {
+            if frames.requested {
+                break;
+            }
+            let deadline = earliest(until, frames.wake_at);
+            match deadline {
+                Some(d) if d <= Instant::now() => break,
+                Some(d) => frames = wake.wait_timeout(frames, d - Instant::now()).unwrap().0,
+                None => frames = wake.wait(frames).unwrap(),
+            }
+        }
+
+        frames.requested = false;
+        if frames.wake_at.is_some_and(|w| w <= Instant::now()) {
+            frames.wake_at = None;
+        }
+        frames.started += 1;
+    }
+
+    // (render thread) the frame that the last wait() started is out
+    pub fn frame_done(&self) {
+        let (frames, wake) = &*self.inner;
+        let mut frames = frames.lock().unwrap();
+        frames.finished = frames.started;
+        wake.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scheduler::Scheduler;
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn sleeps_until_asked() {
+        let scheduler = Scheduler::default();
+        let start = Instant::now();
+        scheduler.wait(Some(start + Duration::from_millis(30)));
+        assert!(start.elapsed() >= Duration::from_millis(30));
+
+        scheduler.request_redraw();
+        let start = Instant::now();
+        scheduler.wait(None);
+        assert!(start.elapsed() < Duration::from_millis(30));
+    }
+
+    #[test]
+    fn redraw_now_waits_for_a_whole_frame() {
+        let scheduler = Scheduler::default();
+        let render = scheduler.clone();
+        let drawn = thread::spawn(move || {
+            render.wait(None);
+            thread::sleep(Duration::from_millis(20));
+            render.frame_done();
+            Instant::now()
+        });
+
+        scheduler.redraw_now();
+        let returned = Instant::now();
+        assert!(drawn.join().unwrap() <= returned);
+    }
+}
@@ -3,5 +3,5 @@ use crate::misc_types::EASHPallete;
 
 // eash god object...
 pub struct EASHState {
-    colors: EASHPallete
-}
\ No newline at end of file
+    colors: EASHPallete,
+}
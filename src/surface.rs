@@ -0,0 +1,315 @@
+use crate::error::EASHError;
+use crossterm::{
+    Command,
+    cursor::{MoveToColumn, MoveUp, SetCursorStyle},
+    queue,
+    style::{Color as ctColor, Colored, Print, SetBackgroundColor, SetForegroundColor},
+    terminal::{Clear, ClearType},
+};
+use std::io::Write;
+use unicode_width::UnicodeWidthStr;
+
+// the colors a cell gets drawn with
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Style {
+    pub fg: ctColor,
+    pub bg: ctColor,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fg: ctColor::Reset,
+            bg: ctColor::Reset,
+        }
+    }
+}
+
+impl Style {
+    pub fn new(fg: ctColor, bg: ctColor) -> Style {
+        Style { fg, bg }
+    }
+}
+
+// something the chain can be drawn onto. row 0 is the line the chain sits on, the rows under it
+// are for the rest of a multi-line prompt. put() moves the column along by however wide the grapheme is
+pub trait Surface {
+    fn move_to(&mut self, column: u16, row: u16) -> Result<(), EASHError>;
+    fn set_style(&mut self, style: Style) -> Result<(), EASHError>;
+    fn put(&mut self, grapheme: &str) -> Result<(), EASHError>;
+    // wipe everything from row 0 down
+    fn clear(&mut self) -> Result<(), EASHError>;
+    fn set_cursor_style(&mut self, style: SetCursorStyle) -> Result<(), EASHError>;
+    fn flush(&mut self) -> Result<(), EASHError>;
+}
+
+// the real terminal. the terminal's cursor has to be on `row` when this gets made
+pub struct CrosstermSurface<W: Write> {
+    w: W,
+    row: u16,
+}
+
+impl<W: Write> CrosstermSurface<W> {
+    pub fn new(w: W, row: u16) -> CrosstermSurface<W> {
+        CrosstermSurface { w, row }
+    }
+}
+
+impl<W: Write> Surface for CrosstermSurface<W> {
+    fn move_to(&mut self, column: u16, row: u16) -> Result<(), EASHError> {
+        if row < self.row {
+            queue!(self.w, MoveUp(self.row - row))?;
+        }
+        // printing newlines (instead of MoveDown) scrolls if we're at the bottom of the terminal
+        for _ in self.row..row {
+            queue!(self.w, Print("\r\n"))?;
+        }
+        self.row = row;
+        queue!(self.w, MoveToColumn(column))?;
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: Style) -> Result<(), EASHError> {
+        queue!(
+            self.w,
+            SetForegroundColor(style.fg),
+            SetBackgroundColor(style.bg)
+        )?;
+        Ok(())
+    }
+
+    fn put(&mut self, grapheme: &str) -> Result<(), EASHError> {
+        queue!(self.w, Print(grapheme))?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), EASHError> {
+        self.move_to(0, 0)?;
+        queue!(self.w, Clear(ClearType::FromCursorDown))?;
+        Ok(())
+    }
+
+    fn set_cursor_style(&mut self, style: SetCursorStyle) -> Result<(), EASHError> {
+        queue!(self.w, style)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), EASHError> {
+        self.w.flush()?;
+        Ok(())
+    }
+}
+
+// the same thing but written out by hand, for when there's no terminal to talk to
+// (dumping a frame into a file or a pipe). starts on row 0
+pub struct AnsiSurface<W: Write> {
+    w: W,
+    row: u16,
+}
+
+impl<W: Write> AnsiSurface<W> {
+    pub fn new(w: W) -> AnsiSurface<W> {
+        AnsiSurface { w, row: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+impl<W: Write> Surface for AnsiSurface<W> {
+    fn move_to(&mut self, column: u16, row: u16) -> Result<(), EASHError> {
+        if row < self.row {
+            write!(self.w, "\x1b[{}A", self.row - row)?;
+        }
+        for _ in self.row..row {
+            write!(self.w, "\r\n")?;
+        }
+        self.row = row;
+        // columns start at 1 over here
+        write!(self.w, "\x1b[{}G", column + 1)?;
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: Style) -> Result<(), EASHError> {
+        write!(
+            self.w,
+            "\x1b[{}m\x1b[{}m",
+            Colored::ForegroundColor(style.fg),
+            Colored::BackgroundColor(style.bg)
+        )?;
+        Ok(())
+    }
+
+    fn put(&mut self, grapheme: &str) -> Result<(), EASHError> {
+        self.w.write_all(grapheme.as_bytes())?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), EASHError> {
+        self.move_to(0, 0)?;
+        write!(self.w, "\x1b[J")?;
+        Ok(())
+    }
+
+    fn set_cursor_style(&mut self, style: SetCursorStyle) -> Result<(), EASHError> {
+        let mut ansi = String::new();
+        // writing to a string can't fail
+        _ = style.write_ansi(&mut ansi);
+        self.w.write_all(ansi.as_bytes())?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), EASHError> {
+        self.w.flush()?;
+        Ok(())
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cell {
+    // empty for the right half of a wide character
+    pub grapheme: String,
+    pub style: Style,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell {
+            grapheme: " ".to_string(),
+            style: Style::default(),
+        }
+    }
+}
+
+// an in-memory terminal, so rendering can be tested without a real one.
+// anything drawn past the right edge gets dropped, rows get added as they're needed
+pub struct GridSurface {
+    pub width: u16,
+    pub rows: Vec<Vec<Cell>>,
+    pub cursor: (u16, u16),
+    pub cursor_style: Option<SetCursorStyle>,
+    style: Style,
+}
+
+impl GridSurface {
+    pub fn new(width: u16) -> GridSurface {
+        GridSurface {
+            width,
+            rows: vec![vec![Cell::default(); width as usize]],
+            cursor: (0, 0),
+            cursor_style: None,
+            style: Style::default(),
+        }
+    }
+
+    pub fn cell(&self, column: u16, row: u16) -> Option<&Cell> {
+        self.rows.get(row as usize)?.get(column as usize)
+    }
+
+    // what's on screen as plain text, with the trailing spaces taken off each row
+    pub fn text(&self) -> String {
+        self.rows
+            .iter()
+            .map(|row| {
+                let line: String = row.iter().map(|c| c.grapheme.as_str()).collect();
+                line.trim_end().to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn grow_to(&mut self, row: u16) {
+        while self.rows.len() <= row as usize {
+            self.rows.push(vec![Cell::default(); self.width as usize]);
+        }
+    }
+}
+
+impl Surface for GridSurface {
+    fn move_to(&mut self, column: u16, row: u16) -> Result<(), EASHError> {
+        self.grow_to(row);
+        self.cursor = (column, row);
+        Ok(())
+    }
+
+    fn set_style(&mut self, style: Style) -> Result<(), EASHError> {
+        self.style = style;
+        Ok(())
+    }
+
+    fn put(&mut self, grapheme: &str) -> Result<(), EASHError> {
+        let (column, row) = self.cursor;
+        let width = grapheme.width() as u16;
+        let style = self.style;
+        let cells = &mut self.rows[row as usize];
+        if let Some(cell) = cells.get_mut(column as usize) {
+            *cell = Cell {
+                grapheme: grapheme.to_string(),
+                style,
+            };
+        }
+        for i in 1..width {
+            if let Some(cell) = cells.get_mut((column + i) as usize) {
+                *cell = Cell {
+                    grapheme: String::new(),
+                    style,
+                };
+            }
+        }
+        self.cursor.0 = column + width;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), EASHError> {
+        self.rows = vec![vec![Cell::default(); self.width as usize]];
+        self.cursor = (0, 0);
+        Ok(())
+    }
+
+    fn set_cursor_style(&mut self, style: SetCursorStyle) -> Result<(), EASHError> {
+        self.cursor_style = Some(style);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), EASHError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::surface::{AnsiSurface, GridSurface, Style, Surface};
+    use crossterm::style::Color as ctColor;
+
+    #[test]
+    fn grid_puts_wide_characters_in_two_cells() {
+        let mut grid = GridSurface::new(8);
+        grid.move_to(1, 0).unwrap();
+        grid.set_style(Style::new(ctColor::Red, ctColor::Reset))
+            .unwrap();
+        for g in ["a", "日", "b"] {
+            grid.put(g).unwrap();
+        }
+        grid.move_to(6, 1).unwrap();
+        // the last one falls off the edge
+        for g in ["x", "y", "z"] {
+            grid.put(g).unwrap();
+        }
+
+        assert_eq!(grid.text(), " a日b\n      xy");
+        assert_eq!(grid.cell(3, 0).unwrap().grapheme, "");
+        assert_eq!(grid.cell(4, 0).unwrap().style.fg, ctColor::Red);
+        assert_eq!(grid.cursor, (9, 1));
+    }
+
+    #[test]
+    fn ansi_moves_relative_to_the_chain() {
+        let mut ansi = AnsiSurface::new(Vec::new());
+        ansi.move_to(2, 1).unwrap();
+        ansi.put("hi").unwrap();
+        ansi.move_to(0, 0).unwrap();
+        let written = String::from_utf8(ansi.into_inner()).unwrap();
+        assert_eq!(written, "\r\n\x1b[3Ghi\x1b[1A\x1b[1G");
+    }
+}
@@ -0,0 +1,67 @@
+// what kind of change is about to happen to the prompt, so we know what to lump together
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EditKind {
+    Typing,
+    Deletion,
+    Paste,
+    Completion,
+    Other,
+}
+
+// everything we need to put the prompt back the way it was
+#[derive(Clone, PartialEq, Debug)]
+pub struct Snapshot {
+    pub prompt: String,
+    pub cursor_position: usize,
+    pub selection_start: Option<usize>,
+}
+
+#[derive(Default)]
+pub struct UndoStack {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    // the kind of the last edit, None if something (like moving the cursor) broke the group
+    last_kind: Option<EditKind>,
+}
+
+impl UndoStack {
+    // call BEFORE changing the prompt, with what it looked like right before the change
+    pub fn record(&mut self, kind: EditKind, before: Snapshot) {
+        // typing a bunch of characters in a row only counts as one step
+        let continues_group = kind == EditKind::Typing && self.last_kind == Some(EditKind::Typing);
+        if !continues_group {
+            self.undo.push(before);
+        }
+
+        self.redo.clear();
+        self.last_kind = Some(kind);
+    }
+
+    // the next edit won't get merged into the previous one
+    pub fn break_group(&mut self) {
+        self.last_kind = None;
+    }
+
+    // hands back the state to go to, skipping anything that wouldn't actually change what you see
+    pub fn undo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        self.last_kind = None;
+        while let Some(previous) = self.undo.pop() {
+            if previous != current {
+                self.redo.push(current);
+                return Some(previous);
+            }
+        }
+        None
+    }
+
+    pub fn redo(&mut self, current: Snapshot) -> Option<Snapshot> {
+        self.last_kind = None;
+        while let Some(next) = self.redo.pop() {
+            if next != current {
+                self.undo.push(current);
+                return Some(next);
+            }
+        }
+        None
+    }
+}
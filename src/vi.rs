@@ -0,0 +1,823 @@
+use crate::{kill_ring::KillRing, misc_types::Direction, prompt::Prompt, undo::EditKind};
+use crossterm::{
+    cursor::SetCursorStyle,
+    event::{KeyCode, KeyEvent, KeyModifiers},
+};
+use serde::Deserialize;
+use unicode_segmentation::UnicodeSegmentation;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ViMode {
+    Insert,
+    Normal,
+    Visual,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub enum CursorShape {
+    Default,
+    Block,
+    Bar,
+    Underline,
+    BlinkingBlock,
+    BlinkingBar,
+    BlinkingUnderline,
+}
+
+impl From<CursorShape> for SetCursorStyle {
+    fn from(value: CursorShape) -> Self {
+        match value {
+            CursorShape::Default => SetCursorStyle::DefaultUserShape,
+            CursorShape::Block => SetCursorStyle::SteadyBlock,
+            CursorShape::Bar => SetCursorStyle::SteadyBar,
+            CursorShape::Underline => SetCursorStyle::SteadyUnderScore,
+            CursorShape::BlinkingBlock => SetCursorStyle::BlinkingBlock,
+            CursorShape::BlinkingBar => SetCursorStyle::BlinkingBar,
+            CursorShape::BlinkingUnderline => SetCursorStyle::BlinkingUnderScore,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct ViConfig {
+    pub enabled: bool,
+    pub insert_cursor: CursorShape,
+    pub normal_cursor: CursorShape,
+    pub visual_cursor: CursorShape,
+}
+
+impl Default for ViConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            insert_cursor: CursorShape::Bar,
+            normal_cursor: CursorShape::Block,
+            visual_cursor: CursorShape::Underline,
+        }
+    }
+}
+
+impl ViConfig {
+    pub fn cursor_for(&self, mode: ViMode) -> SetCursorStyle {
+        match mode {
+            ViMode::Insert => self.insert_cursor,
+            ViMode::Normal => self.normal_cursor,
+            ViMode::Visual => self.visual_cursor,
+        }
+        .into()
+    }
+}
+
+// what main should do after we've looked at a key
+pub enum ViResult {
+    Handled,
+    Bump(Direction),
+    PassThrough, // not ours, handle it like a normal key
+}
+
+#[derive(Clone, Copy)]
+enum Motion {
+    Left,
+    Right,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    LineEnd,
+    Find { kind: char, target: char }, // f, t, F or T
+}
+
+// stuff that's waiting on another key
+#[derive(Clone, Copy, PartialEq)]
+enum Pending {
+    Nothing,
+    Find(char),
+    TextObject,
+}
+
+// counts stop growing here, nobody means 99999999999w
+const MAX_COUNT: usize = 9999;
+// and a counted put can't put in more than this many bytes
+const MAX_PUT: usize = 1 << 20;
+
+pub struct ViState {
+    count: Option<usize>,
+    operator: Option<(char, usize)>, // the operator and the count that came before it
+    pending: Pending,
+}
+
+impl Default for ViState {
+    fn default() -> Self {
+        Self {
+            count: None,
+            operator: None,
+            pending: Pending::Nothing,
+        }
+    }
+}
+
+// 0 = whitespace, 1 = word characters, 2 = everything else. what vim calls a "word" is a run of one class.
+fn class(grapheme: &str) -> u8 {
+    let c = grapheme.chars().next().unwrap_or(' ');
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+// the prompt as a list of (byte position, grapheme), motions are a lot easier to write with indices
+fn graphemes(s: &str) -> Vec<(usize, &str)> {
+    s.grapheme_indices(true).collect()
+}
+
+fn index_of(gs: &[(usize, &str)], position: usize) -> usize {
+    gs.iter()
+        .position(|(p, _)| *p >= position)
+        .unwrap_or(gs.len())
+}
+
+fn position_of(gs: &[(usize, &str)], index: usize, len: usize) -> usize {
+    gs.get(index).map(|(p, _)| *p).unwrap_or(len)
+}
+
+fn word_forward(gs: &[(usize, &str)], mut i: usize) -> usize {
+    let n = gs.len();
+    if i >= n {
+        return n;
+    }
+    let c = class(gs[i].1);
+    if c != 0 {
+        while i < n && class(gs[i].1) == c {
+            i += 1;
+        }
+    }
+    while i < n && class(gs[i].1) == 0 {
+        i += 1;
+    }
+    i
+}
+
+fn word_backward(gs: &[(usize, &str)], mut i: usize) -> usize {
+    if i == 0 {
+        return 0;
+    }
+    i -= 1;
+    while i > 0 && class(gs[i].1) == 0 {
+        i -= 1;
+    }
+    let c = class(gs[i].1);
+    while i > 0 && class(gs[i - 1].1) == c {
+        i -= 1;
+    }
+    i
+}
+
+fn word_end(gs: &[(usize, &str)], mut i: usize) -> usize {
+    let n = gs.len();
+    if i + 1 >= n {
+        return n.saturating_sub(1);
+    }
+    i += 1;
+    while i < n && class(gs[i].1) == 0 {
+        i += 1;
+    }
+    if i >= n {
+        return n - 1;
+    }
+    let c = class(gs[i].1);
+    while i + 1 < n && class(gs[i + 1].1) == c {
+        i += 1;
+    }
+    i
+}
+
+fn find(gs: &[(usize, &str)], i: usize, kind: char, target: char) -> Option<usize> {
+    let is_target = |j: usize| gs[j].1.starts_with(target);
+    match kind {
+        'f' => (i + 1..gs.len()).find(|j| is_target(*j)),
+        't' => (i + 2..gs.len()).find(|j| is_target(*j)).map(|j| j - 1),
+        'F' => (0..i).rev().find(|j| is_target(*j)),
+        'T' => (0..i.saturating_sub(1))
+            .rev()
+            .find(|j| is_target(*j))
+            .map(|j| j + 1),
+        _ => None,
+    }
+}
+
+impl Motion {
+    // where the cursor ends up (as a grapheme index), and whether the grapheme there is part of the motion
+    fn apply(&self, gs: &[(usize, &str)], mut i: usize, count: usize) -> Option<(usize, bool)> {
+        let n = gs.len();
+        for _ in 0..count {
+            let next = match self {
+                Motion::Left => i.saturating_sub(1),
+                Motion::Right => (i + 1).min(n),
+                Motion::WordForward => word_forward(gs, i),
+                Motion::WordBackward => word_backward(gs, i),
+                Motion::WordEnd => word_end(gs, i),
+                // (the line the cursor's on, not the whole prompt)
+                Motion::LineStart => gs[..i.min(n)]
+                    .iter()
+                    .rposition(|(_, g)| *g == "\n")
+                    .map_or(0, |j| j + 1),
+                Motion::LineEnd => gs[i.min(n)..]
+                    .iter()
+                    .position(|(_, g)| *g == "\n")
+                    .map_or(n, |j| i + j),
+                Motion::Find { kind, target } => find(gs, i, *kind, *target)?,
+            };
+            // (ran into the end of something, the rest of the count won't get any further)
+            if next == i {
+                break;
+            }
+            i = next;
+        }
+
+        let inclusive = matches!(
+            self,
+            Motion::WordEnd
+                | Motion::Find {
+                    kind: 'f' | 't',
+                    ..
+                }
+        );
+        Some((i, inclusive))
+    }
+}
+
+// the range (in bytes) for text objects like iw, i" and i(
+fn text_object(prompt: &Prompt, object: char) -> Option<(usize, usize)> {
+    let gs = graphemes(prompt.text());
+    if gs.is_empty() {
+        return None;
+    }
+    let len = prompt.text().len();
+    let i = index_of(&gs, prompt.cursor_position).min(gs.len() - 1);
+
+    match object {
+        'w' => {
+            let c = class(gs.get(i)?.1);
+            let mut start = i;
+            while start > 0 && class(gs[start - 1].1) == c {
+                start -= 1;
+            }
+            let mut end = i;
+            while end < gs.len() && class(gs[end].1) == c {
+                end += 1;
+            }
+            Some((position_of(&gs, start, len), position_of(&gs, end, len)))
+        }
+        '"' | '\'' | '`' => {
+            let q = object.to_string();
+            let before = gs[..=i].iter().rposition(|(_, g)| *g == q)?;
+            let after = gs[i + 1..].iter().position(|(_, g)| *g == q)? + i + 1;
+            Some((
+                position_of(&gs, before + 1, len),
+                position_of(&gs, after, len),
+            ))
+        }
+        '(' | ')' | 'b' | '[' | ']' | '{' | '}' | 'B' => {
+            let (open, close) = match object {
+                '(' | ')' | 'b' => ("(", ")"),
+                '[' | ']' => ("[", "]"),
+                _ => ("{", "}"),
+            };
+
+            // walk out to the opening bracket that holds the cursor
+            let mut depth = 0;
+            let mut start = None;
+            for j in (0..=i).rev() {
+                if gs[j].1 == close && j != i {
+                    depth += 1;
+                } else if gs[j].1 == open {
+                    if depth == 0 {
+                        start = Some(j);
+                        break;
+                    }
+                    depth -= 1;
+                }
+            }
+            let start = start?;
+
+            let mut depth = 0;
+            for j in start + 1..gs.len() {
+                if gs[j].1 == open {
+                    depth += 1;
+                } else if gs[j].1 == close {
+                    if depth == 0 {
+                        return Some((position_of(&gs, start + 1, len), position_of(&gs, j, len)));
+                    }
+                    depth -= 1;
+                }
+            }
+            None
+        }
+        _ => None,
+    }
+}
+
+impl ViState {
+    fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.pending = Pending::Nothing;
+    }
+
+    fn set_mode(prompt: &mut Prompt, mode: ViMode) {
+        match mode {
+            ViMode::Visual => prompt.selection_start = Some(prompt.cursor_position),
+            _ => prompt.selection_start = None,
+        }
+        prompt.edits.break_group();
+        prompt.vi_mode = Some(mode);
+    }
+
+    // in normal mode the cursor sits ON a character, so it can't be past the end of its line
+    // (unless the line's empty, then there's nothing else to sit on)
+    fn clamp(prompt: &mut Prompt) {
+        let at = prompt.cursor_position;
+        let past_the_end = at >= prompt.text().len() || prompt.text()[at..].starts_with('\n');
+        if prompt.vi_mode != Some(ViMode::Insert) && past_the_end && at > prompt.line_start() {
+            prompt.cursor_position = prompt.previous_boundary(at);
+        }
+    }
+
+    // d, c or y over a byte range
+    fn operate(
+        prompt: &mut Prompt,
+        kill_ring: &mut KillRing,
+        operator: char,
+        (start, end): (usize, usize),
+    ) {
+        if operator == 'y' {
+            let text = prompt.text()[start..end].to_string();
+            kill_ring.kill(text, Direction::Right, false, prompt.text(), start);
+            prompt.selection_start = None;
+            prompt.cursor_position = start;
+            return;
+        }
+
+        let killed = prompt.kill_range(start, end);
+        kill_ring.kill(killed, Direction::Right, false, prompt.text(), start);
+        if operator == 'c' {
+            Self::set_mode(prompt, ViMode::Insert);
+        }
+    }
+
+    fn take_count(&mut self) -> usize {
+        self.count.take().unwrap_or(1)
+    }
+
+    // a motion either moves the cursor or, if an operator is waiting, says what it should work on
+    fn motion(
+        &mut self,
+        prompt: &mut Prompt,
+        kill_ring: &mut KillRing,
+        motion: Motion,
+    ) -> ViResult {
+        let gs = graphemes(prompt.text());
+        let len = prompt.text().len();
+        let i = index_of(&gs, prompt.cursor_position);
+
+        let mut count = self.take_count();
+        let operator = self.operator.take();
+        if let Some((_, c)) = operator {
+            count = count.saturating_mul(c).min(MAX_COUNT);
+        }
+
+        // cw acts like ce, for some reason
+        let motion = match (operator, motion) {
+            (Some(('c', _)), Motion::WordForward)
+                if class(gs.get(i).map(|g| g.1).unwrap_or(" ")) != 0 =>
+            {
+                Motion::WordEnd
+            }
+            _ => motion,
+        };
+
+        let Some((target, inclusive)) = motion.apply(&gs, i, count) else {
+            return ViResult::Bump(Direction::Right);
+        };
+
+        match operator {
+            Some((op, _)) => {
+                let (from, to) = (i.min(target), i.max(target));
+                let to = if inclusive { to + 1 } else { to };
+                Self::operate(
+                    prompt,
+                    kill_ring,
+                    op,
+                    (position_of(&gs, from, len), position_of(&gs, to, len)),
+                );
+            }
+            None => {
+                let before = prompt.cursor_position;
+                prompt.cursor_position = position_of(&gs, target, len);
+                prompt.edits.break_group();
+                Self::clamp(prompt);
+                if before == prompt.cursor_position {
+                    let direction = if target < i {
+                        Direction::Left
+                    } else {
+                        Direction::Right
+                    };
+                    return ViResult::Bump(direction);
+                }
+            }
+        }
+
+        ViResult::Handled
+    }
+
+    pub fn handle_key(
+        &mut self,
+        prompt: &mut Prompt,
+        kill_ring: &mut KillRing,
+        key: &KeyEvent,
+    ) -> ViResult {
+        let Some(mode) = prompt.vi_mode else {
+            return ViResult::PassThrough;
+        };
+
+        if mode == ViMode::Insert {
+            if key.code == KeyCode::Esc {
+                Self::set_mode(prompt, ViMode::Normal);
+                prompt.cursor_position = prompt.previous_boundary(prompt.cursor_position);
+                return ViResult::Handled;
+            }
+            return ViResult::PassThrough;
+        }
+
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let result = match key.code {
+            KeyCode::Esc => {
+                self.reset();
+                Self::set_mode(prompt, ViMode::Normal);
+                ViResult::Handled
+            }
+            KeyCode::Char('r') if ctrl => match prompt.redo() {
+                true => ViResult::Bump(Direction::Right),
+                false => ViResult::Handled,
+            },
+            KeyCode::Char(c) if !ctrl => self.handle_char(prompt, kill_ring, mode, c),
+            KeyCode::Backspace => self.motion(prompt, kill_ring, Motion::Left),
+            _ => ViResult::PassThrough,
+        };
+
+        Self::clamp(prompt);
+        result
+    }
+
+    fn handle_char(
+        &mut self,
+        prompt: &mut Prompt,
+        kill_ring: &mut KillRing,
+        mode: ViMode,
+        c: char,
+    ) -> ViResult {
+        match self.pending {
+            Pending::Find(kind) => {
+                self.pending = Pending::Nothing;
+                return self.motion(prompt, kill_ring, Motion::Find { kind, target: c });
+            }
+            Pending::TextObject => {
+                self.pending = Pending::Nothing;
+                let Some((start, end)) = text_object(prompt, c) else {
+                    self.reset();
+                    return ViResult::Bump(Direction::Right);
+                };
+                if mode == ViMode::Visual {
+                    prompt.selection_start = Some(start);
+                    prompt.cursor_position = if start == end {
+                        start
+                    } else {
+                        prompt.previous_boundary(end)
+                    };
+                } else if let Some((op, _)) = self.operator.take() {
+                    Self::operate(prompt, kill_ring, op, (start, end));
+                }
+                self.count = None;
+                return ViResult::Handled;
+            }
+            Pending::Nothing => {}
+        }
+
+        // counts. a 0 on its own is "go to the start of the line"
+        if let Some(digit) = c.to_digit(10)
+            && (digit != 0 || self.count.is_some())
+        {
+            let count = self.count.unwrap_or(0).saturating_mul(10);
+            self.count = Some(count.saturating_add(digit as usize).min(MAX_COUNT));
+            return ViResult::Handled;
+        }
+
+        let motion = match c {
+            'h' => Some(Motion::Left),
+            'l' | ' ' => Some(Motion::Right),
+            'w' => Some(Motion::WordForward),
+            'b' => Some(Motion::WordBackward),
+            'e' => Some(Motion::WordEnd),
+            '0' => Some(Motion::LineStart),
+            '$' => Some(Motion::LineEnd),
+            _ => None,
+        };
+        if let Some(m) = motion {
+            return self.motion(prompt, kill_ring, m);
+        }
+
+        if matches!(c, 'f' | 't' | 'F' | 'T') {
+            self.pending = Pending::Find(c);
+            return ViResult::Handled;
+        }
+
+        if mode == ViMode::Visual {
+            return self.visual_command(prompt, kill_ring, c);
+        }
+
+        // operators, doubling them up (dd, cc, yy) does the whole line
+        if matches!(c, 'd' | 'c' | 'y') {
+            match self.operator {
+                Some((op, _)) if op == c => {
+                    self.reset();
+                    let (start, end) = (prompt.line_start(), prompt.line_end());
+                    // dd takes a newline with it, so the lines on either side close up
+                    let range = match c {
+                        'd' if end < prompt.text().len() => (start, end + 1),
+                        'd' if start > 0 => (start - 1, end),
+                        _ => (start, end),
+                    };
+                    Self::operate(prompt, kill_ring, c, range);
+                }
+                Some(_) => self.reset(),
+                None => self.operator = Some((c, self.take_count())),
+            }
+            return ViResult::Handled;
+        }
+
+        if c == 'i' && self.operator.is_some() {
+            self.pending = Pending::TextObject;
+            return ViResult::Handled;
+        }
+
+        // anything else throws away a half typed operator
+        self.operator = None;
+        let count = self.take_count();
+        let len = prompt.text().len();
+        let cursor = prompt.cursor_position;
+        let (line_start, line_end) = (prompt.line_start(), prompt.line_end());
+
+        match c {
+            'i' => Self::set_mode(prompt, ViMode::Insert),
+            'a' => {
+                Self::set_mode(prompt, ViMode::Insert);
+                prompt.cursor_position = prompt.next_boundary(cursor);
+            }
+            'I' => {
+                Self::set_mode(prompt, ViMode::Insert);
+                prompt.cursor_position = line_start;
+            }
+            'A' => {
+                Self::set_mode(prompt, ViMode::Insert);
+                prompt.cursor_position = line_end;
+            }
+            'v' => Self::set_mode(prompt, ViMode::Visual),
+            'x' | 's' => {
+                let gs = graphemes(prompt.text());
+                let i = index_of(&gs, cursor);
+                let end = position_of(&gs, (i + count).min(gs.len()), len);
+                let op = if c == 'x' { 'd' } else { 'c' };
+                if cursor == end && op == 'd' {
+                    return ViResult::Bump(Direction::Right);
+                }
+                Self::operate(prompt, kill_ring, op, (cursor, end));
+            }
+            'X' => {
+                let gs = graphemes(prompt.text());
+                let i = index_of(&gs, cursor);
+                let start = position_of(&gs, i.saturating_sub(count), len);
+                if start == cursor {
+                    return ViResult::Bump(Direction::Left);
+                }
+                Self::operate(prompt, kill_ring, 'd', (start, cursor));
+            }
+            'D' => Self::operate(prompt, kill_ring, 'd', (cursor, line_end)),
+            'C' => Self::operate(prompt, kill_ring, 'c', (cursor, line_end)),
+            'S' => Self::operate(prompt, kill_ring, 'c', (line_start, line_end)),
+            'p' | 'P' => {
+                if c == 'p' && !prompt.text().is_empty() {
+                    prompt.cursor_position = prompt.next_boundary(cursor);
+                }
+                let Some(text) = kill_ring.yank(prompt.cursor_position) else {
+                    return ViResult::Bump(Direction::Right);
+                };
+                if text.len().saturating_mul(count) > MAX_PUT {
+                    return ViResult::Bump(Direction::Right);
+                }
+                let text = text.repeat(count);
+                prompt.insert_str(&text, EditKind::Paste);
+                // vim leaves you on the last character that got put in
+                prompt.cursor_position = prompt.previous_boundary(prompt.cursor_position);
+            }
+            'u' => {
+                for _ in 0..count {
+                    if prompt.undo() {
+                        return ViResult::Bump(Direction::Left);
+                    }
+                }
+            }
+            _ => return ViResult::Bump(Direction::Right),
+        }
+
+        ViResult::Handled
+    }
+
+    fn visual_command(
+        &mut self,
+        prompt: &mut Prompt,
+        kill_ring: &mut KillRing,
+        c: char,
+    ) -> ViResult {
+        self.count = None;
+        match c {
+            'i' => self.pending = Pending::TextObject,
+            'v' => Self::set_mode(prompt, ViMode::Normal),
+            'd' | 'x' | 'c' | 's' | 'y' => {
+                let Some(range) = prompt.selection_range() else {
+                    return ViResult::Bump(Direction::Right);
+                };
+                let op = match c {
+                    'd' | 'x' => 'd',
+                    'c' | 's' => 'c',
+                    _ => 'y',
+                };
+                Self::operate(prompt, kill_ring, op, range);
+                if op != 'c' {
+                    Self::set_mode(prompt, ViMode::Normal);
+                }
+            }
+            _ => return ViResult::Bump(Direction::Right),
+        }
+        ViResult::Handled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        kill_ring::KillRing,
+        prompt::Prompt,
+        vi::{ViMode, ViResult, ViState},
+    };
+    use crossterm::event::{KeyCode, KeyEvent};
+
+    fn normal(text: &str, cursor: usize) -> Prompt {
+        let mut p = Prompt::default();
+        p.insert_str(text, crate::undo::EditKind::Other);
+        p.edits = Default::default();
+        p.vi_mode = Some(ViMode::Normal);
+        p.cursor_position = cursor;
+        p
+    }
+
+    fn keys(p: &mut Prompt, ring: &mut KillRing, keys: &str) {
+        let mut vi = ViState::default();
+        for c in keys.chars() {
+            let code = if c == '\x1b' {
+                KeyCode::Esc
+            } else {
+                KeyCode::Char(c)
+            };
+            // main does the typing in insert mode
+            if let ViResult::PassThrough = vi.handle_key(p, ring, &KeyEvent::from(code)) {
+                p.insert_character(c);
+            }
+        }
+    }
+
+    #[test]
+    fn operators_with_motions_and_counts() {
+        let mut ring = KillRing::default();
+
+        let mut p = normal("git commit --amend -m wip", 0);
+        keys(&mut p, &mut ring, "w2dw");
+        assert_eq!(p.text(), "git amend -m wip");
+
+        let mut p = normal("git commit --amend", 4);
+        keys(&mut p, &mut ring, "cwpush\x1b");
+        assert_eq!(p.text(), "git push --amend");
+        assert_eq!(p.vi_mode, Some(ViMode::Normal));
+        assert_eq!(p.cursor_position, 7);
+
+        let mut p = normal("echo hello/world", 0);
+        keys(&mut p, &mut ring, "dt/");
+        assert_eq!(p.text(), "/world");
+
+        let mut p = normal("echo hello/world", 0);
+        keys(&mut p, &mut ring, "$F/D");
+        assert_eq!(p.text(), "echo hello");
+        assert_eq!(p.cursor_position, 9);
+    }
+
+    #[test]
+    fn line_commands_stay_on_the_cursors_line() {
+        let mut ring = KillRing::default();
+        let text = "echo one\necho two";
+
+        let mut p = normal(text, 11);
+        keys(&mut p, &mut ring, "0");
+        assert_eq!(p.cursor_position, 9);
+        keys(&mut p, &mut ring, "$");
+        assert_eq!(p.cursor_position, 16);
+        let mut p = normal(text, 2);
+        keys(&mut p, &mut ring, "$");
+        assert_eq!(p.cursor_position, 7);
+
+        let mut p = normal(text, 2);
+        keys(&mut p, &mut ring, "A!\x1b");
+        assert_eq!(p.text(), "echo one!\necho two");
+        let mut p = normal(text, 11);
+        keys(&mut p, &mut ring, "Isudo \x1b");
+        assert_eq!(p.text(), "echo one\nsudo echo two");
+
+        let mut p = normal(text, 2);
+        keys(&mut p, &mut ring, "d$");
+        assert_eq!(p.text(), "ec\necho two");
+        let mut p = normal(text, 11);
+        keys(&mut p, &mut ring, "D");
+        assert_eq!(p.text(), "echo one\nec");
+
+        let mut p = normal(text, 2);
+        keys(&mut p, &mut ring, "dd");
+        assert_eq!(p.text(), "echo two");
+        let mut p = normal(text, 11);
+        keys(&mut p, &mut ring, "dd");
+        assert_eq!(p.text(), "echo one");
+        let mut p = normal(text, 11);
+        keys(&mut p, &mut ring, "ccls\x1b");
+        assert_eq!(p.text(), "echo one\nls");
+        let mut p = normal(text, 2);
+        keys(&mut p, &mut ring, "yy");
+        assert_eq!(ring.yank(0).as_deref(), Some("echo one"));
+    }
+
+    #[test]
+    fn huge_counts_stop_at_the_cap() {
+        let mut ring = KillRing::default();
+
+        // (this used to overflow, or sit there moving right a few quintillion times)
+        let mut p = normal("one two three", 0);
+        keys(&mut p, &mut ring, "99999999999999999999999w");
+        assert_eq!(p.cursor_position, 12);
+        let mut p = normal("one two three", 0);
+        keys(&mut p, &mut ring, "99999d99999w");
+        assert_eq!(p.text(), "");
+
+        // a put that would be enormous doesn't happen at all
+        let mut p = normal("abc", 0);
+        keys(&mut p, &mut ring, "yw9999p");
+        assert_eq!(p.text(), "a".to_string() + &"abc".repeat(9999) + "bc");
+        let mut p = normal(&"x".repeat(1000), 0);
+        keys(&mut p, &mut ring, "y$9999p");
+        assert_eq!(p.text().len(), 1000);
+    }
+
+    #[test]
+    fn text_objects() {
+        let mut ring = KillRing::default();
+
+        let mut p = normal("echo \"some words\" after", 8);
+        keys(&mut p, &mut ring, "di\"");
+        assert_eq!(p.text(), "echo \"\" after");
+
+        let mut p = normal("echo $(ls (nested) dir) x", 12);
+        keys(&mut p, &mut ring, "ci(");
+        assert_eq!(p.text(), "echo $(ls () dir) x");
+        assert_eq!(p.vi_mode, Some(ViMode::Insert));
+
+        let mut p = normal("echo $(ls (nested) dir) x", 20);
+        keys(&mut p, &mut ring, "yi(");
+        keys(&mut p, &mut ring, "$p");
+        assert_eq!(p.text(), "echo $(ls (nested) dir) xls (nested) dir");
+
+        let mut p = normal("one two three", 5);
+        keys(&mut p, &mut ring, "diw");
+        assert_eq!(p.text(), "one  three");
+    }
+
+    #[test]
+    fn visual_mode_uses_the_selection() {
+        let mut ring = KillRing::default();
+        let mut p = normal("abcdef", 1);
+        keys(&mut p, &mut ring, "vl");
+        assert_eq!(p.selection_range(), Some((1, 3)));
+        keys(&mut p, &mut ring, "d");
+        assert_eq!(p.text(), "adef");
+        assert_eq!(p.vi_mode, Some(ViMode::Normal));
+        assert_eq!(p.selection_start, None);
+    }
+}